@@ -7,6 +7,21 @@ pub struct MainCamera;
 #[derive(Component)]
 pub struct CameraTarget;
 
+const MAX_SHAKE_OFFSET: f32 = 6.;
+const TRAUMA_DECAY: f32 = 1.5; // per second
+
+// trauma goes from 0 to 1, the shake grows with its square so small hits stay subtle
+#[derive(Component, Default)]
+pub struct CameraShake {
+  pub trauma: f32,
+  pub offset: Vec2, // currently applied to the camera
+}
+impl CameraShake {
+  pub fn add_trauma(&mut self, amount: f32) {
+    self.trauma = (self.trauma + amount).min(1.);
+  }
+}
+
 pub fn setup_camera(mut commands: Commands) {
   let mut cam_bundle = OrthographicCameraBundle::new_2d();
   cam_bundle.transform = cam_bundle.transform.with_scale(Vec3::new(0.25, 0.25, 1.0));
@@ -14,6 +29,7 @@ pub fn setup_camera(mut commands: Commands) {
   commands
     .spawn_bundle(cam_bundle)
     .insert(MainCamera)
+    .insert(CameraShake::default())
     .insert(Movement {
       speed: 600.0,
       enabled: true,
@@ -44,3 +60,19 @@ pub fn camera_system_initial_focus(
     }
   }
 }
+
+// runs on real time, the shake keeps going during hitstop
+pub fn shake_camera(
+  time: Res<Time>,
+  mut qry: Query<(&mut CameraShake, &mut Transform), With<MainCamera>>,
+) {
+  for (mut shake, mut transform) in qry.iter_mut() {
+    // take back last frame's offset so it doesn't pile up on top of the camera movement
+    transform.translation -= Vec3::from((shake.offset, 0.));
+    shake.trauma = (shake.trauma - TRAUMA_DECAY * time.delta_seconds()).max(0.);
+    let t = time.seconds_since_startup() as f32;
+    let strength = shake.trauma * shake.trauma * MAX_SHAKE_OFFSET;
+    shake.offset = Vec2::new((t * 47.).sin(), (t * 59. + 1.3).sin()) * strength;
+    transform.translation += Vec3::from((shake.offset, 0.));
+  }
+}
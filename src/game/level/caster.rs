@@ -0,0 +1,128 @@
+use super::LevelState;
+use crate::systems::{CombatAction, GameTime, Movement, SpellType, Spellbook, Stunned};
+use bevy::prelude::*;
+
+// casts a single spell at whatever its owner picks as the target, used by enemies and minions
+#[derive(Component)]
+pub struct Caster {
+  pub spell: SpellType,
+  pub range: f32, // start casting once the target is this close
+  pub state: CasterState,
+  pub direction: Vec2,
+  pub timer: Timer,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum CasterState {
+  Idle,
+  Preparing,
+  Casting,
+  Recovering,
+}
+
+impl Caster {
+  pub fn new(spell: SpellType, range: f32) -> Self {
+    Caster {
+      spell,
+      range,
+      state: CasterState::Idle,
+      direction: Vec2::ZERO,
+      timer: Timer::from_seconds(0., false),
+    }
+  }
+
+  // starts winding up if the caster is idle, the target is in range and the spell is ready,
+  // returns false if the caster can't attack the target right now
+  pub fn try_cast(
+    &mut self,
+    entity: Entity,
+    position: Vec2,
+    target: Vec2,
+    spellbook: &Spellbook,
+    mov: &mut Movement,
+    evts: &mut EventWriter<CombatAction>,
+  ) -> bool {
+    let spell = match spellbook.spells.get(&self.spell) {
+      Some(spell) => spell,
+      None => return false,
+    };
+    let diff = target - position;
+    if self.state != CasterState::Idle || diff.length() > self.range || !spell.is_ready() {
+      return false;
+    }
+
+    // stand still while winding up so the target can react
+    mov.enabled = false;
+    self.direction = diff.normalize_or_zero();
+    self.state = CasterState::Preparing;
+    self.timer = Timer::from_seconds(spell.prepare_duration, false);
+    evts.send(CombatAction::PrepareSpell(entity, self.spell, self.direction));
+    true
+  }
+}
+
+// targets are picked by the owners of the casters, this takes care of the rest of the cast
+fn advance_casters(
+  time: Res<GameTime>,
+  mut qry: Query<(Entity, &mut Caster, &Spellbook, &mut Movement), Without<Stunned>>,
+  mut evts: EventWriter<CombatAction>,
+) {
+  for (entity, mut caster, spellbook, mut mov) in qry.iter_mut() {
+    if caster.state == CasterState::Idle {
+      continue;
+    }
+    let spell_type = caster.spell;
+    let spell = match spellbook.spells.get(&spell_type) {
+      Some(spell) => spell,
+      None => continue,
+    };
+    caster.timer.tick(time.delta());
+    if !caster.timer.just_finished() {
+      continue;
+    }
+
+    match caster.state {
+      CasterState::Idle => {}
+      CasterState::Preparing => {
+        caster.state = CasterState::Casting;
+        caster.timer = Timer::from_seconds(spell.cast_duration, false);
+        evts.send(CombatAction::CastSpell(entity, spell_type, caster.direction));
+      }
+      CasterState::Casting => {
+        caster.state = CasterState::Recovering;
+        caster.timer = Timer::from_seconds(spell.recovery_duration, false);
+        evts.send(CombatAction::RecoverFromSpell(entity, spell_type, caster.direction));
+      }
+      CasterState::Recovering => {
+        caster.state = CasterState::Idle;
+        mov.enabled = true;
+      }
+    }
+  }
+}
+
+// a stun interrupts the cast, the caster starts over from idle once it wears off
+fn interrupt_casters(
+  mut qry: Query<(Entity, &mut Caster, &mut Movement), Added<Stunned>>,
+  mut evts: EventWriter<CombatAction>,
+) {
+  for (entity, mut caster, mut mov) in qry.iter_mut() {
+    if caster.state == CasterState::Idle {
+      continue;
+    }
+    caster.state = CasterState::Idle;
+    mov.enabled = true;
+    evts.send(CombatAction::CancelSpell(entity));
+  }
+}
+
+pub struct CasterPlugin;
+impl Plugin for CasterPlugin {
+  fn build(&self, app: &mut App) {
+    app.add_system_set(
+      SystemSet::on_update(LevelState::Loaded)
+        .with_system(interrupt_casters.before("advance_casters"))
+        .with_system(advance_casters.label("advance_casters")),
+    );
+  }
+}
@@ -0,0 +1,194 @@
+use super::{
+  dungeon::Dungeon, enemy::Enemy, minion::Minion, player::PlayerComponent, secrets::BreakableWall,
+};
+use crate::systems::{CombatEvent, DamageType, SpellType};
+use bevy::{prelude::*, utils::HashMap};
+use bevy_egui::{egui, EguiContext};
+use std::{
+  collections::VecDeque,
+  fmt::Write,
+  fs,
+  path::Path,
+  time::{SystemTime, UNIX_EPOCH},
+};
+
+pub struct CombatLogEntry {
+  pub time: f64, // seconds since the level was loaded
+  pub source: String,
+  pub target: String,
+  pub spell: Option<SpellType>,
+  pub amount: f32,
+  pub overkill: f32,
+  pub damage_type: DamageType,
+  pub crit: bool,
+  pub fatal: bool,
+}
+
+// every hit of the current level, oldest entries are dropped once it is full
+pub struct CombatLog {
+  pub max_size: usize,
+  pub entries: VecDeque<CombatLogEntry>,
+  pub level_started: f64,
+  pub show_meter: bool,
+}
+impl Default for CombatLog {
+  fn default() -> Self {
+    CombatLog {
+      max_size: 10000,
+      entries: VecDeque::new(),
+      level_started: 0.,
+      show_meter: false,
+    }
+  }
+}
+
+impl CombatLog {
+  pub fn to_csv(&self) -> String {
+    let mut csv = "time,source,target,spell,amount,overkill,damage_type,crit,fatal\n".to_owned();
+    for entry in self.entries.iter() {
+      let _ = writeln!(
+        csv,
+        "{:.3},{},{},{},{:.1},{:.1},{:?},{},{}",
+        entry.time,
+        entry.source,
+        entry.target,
+        entry.spell.map_or(String::new(), |spell| format!("{:?}", spell)),
+        entry.amount,
+        entry.overkill,
+        entry.damage_type,
+        entry.crit,
+        entry.fatal
+      );
+    }
+    csv
+  }
+}
+
+pub fn reset_combat_log(time: Res<Time>, mut log: ResMut<CombatLog>) {
+  log.entries.clear();
+  log.level_started = time.seconds_since_startup();
+}
+
+pub fn record_combat_log(
+  time: Res<Time>,
+  mut log: ResMut<CombatLog>,
+  mut events: EventReader<CombatEvent>,
+  qry_player: Query<&PlayerComponent>,
+  qry_enemies: Query<&Enemy>,
+  qry_walls: Query<&BreakableWall>,
+  qry_minions: Query<&Minion>,
+) {
+  let name = |entity: Entity| {
+    if qry_player.get(entity).is_ok() {
+      "Player".to_owned()
+    } else if let Ok(enemy) = qry_enemies.get(entity) {
+      format!("{:?}", enemy.enemy_type)
+    } else if qry_walls.get(entity).is_ok() {
+      "Wall".to_owned()
+    } else if qry_minions.get(entity).is_ok() {
+      "Minion".to_owned()
+    } else {
+      "Unknown".to_owned()
+    }
+  };
+
+  for evt in events.iter() {
+    if let CombatEvent::DamageApplied(damage) = evt {
+      if log.entries.len() >= log.max_size {
+        log.entries.pop_front();
+      }
+      let entry = CombatLogEntry {
+        time: time.seconds_since_startup() - log.level_started,
+        source: name(damage.source),
+        target: name(damage.victim),
+        spell: damage.spell,
+        amount: damage.amount,
+        overkill: damage.overkill,
+        damage_type: damage.damage_type,
+        crit: damage.crit,
+        fatal: damage.fatal,
+      };
+      log.entries.push_back(entry);
+    }
+  }
+}
+
+// F2 shows the damage meter
+pub fn toggle_damage_meter(keyboard_input: Res<Input<KeyCode>>, mut log: ResMut<CombatLog>) {
+  if keyboard_input.just_pressed(KeyCode::F2) {
+    log.show_meter = !log.show_meter;
+  }
+}
+
+pub fn show_damage_meter(
+  time: Res<Time>,
+  log: Res<CombatLog>,
+  mut egui_context: ResMut<EguiContext>,
+) {
+  if !log.show_meter {
+    return;
+  }
+  let elapsed = (time.seconds_since_startup() - log.level_started).max(1.) as f32;
+
+  // damage and overkill per spell, only the player's hits count
+  let mut spells: HashMap<String, (f32, f32)> = HashMap::default();
+  let mut kills: HashMap<String, u32> = HashMap::default();
+  for entry in log.entries.iter().filter(|entry| entry.source == "Player") {
+    let spell = entry.spell.map_or("Other".to_owned(), |spell| format!("{:?}", spell));
+    let totals = spells.entry(spell).or_insert((0., 0.));
+    totals.0 += entry.amount;
+    totals.1 += entry.overkill;
+    if entry.fatal {
+      *kills.entry(entry.target.clone()).or_insert(0) += 1;
+    }
+  }
+  let mut spells: Vec<_> = spells.into_iter().collect();
+  spells.sort_by(|a, b| b.1 .0.partial_cmp(&a.1 .0).unwrap_or(std::cmp::Ordering::Equal));
+  let mut kills: Vec<_> = kills.into_iter().collect();
+  kills.sort_by(|a, b| b.1.cmp(&a.1));
+
+  egui::Window::new("Damage meter").show(egui_context.ctx_mut(), |ui| {
+    egui::Grid::new("damage_meter_spells").striped(true).show(ui, |ui| {
+      ui.label("Spell");
+      ui.label("Damage");
+      ui.label("DPS");
+      ui.label("Overkill");
+      ui.end_row();
+      for (spell, (damage, overkill)) in spells.iter() {
+        ui.label(spell.as_str());
+        ui.label(format!("{:.0}", damage));
+        ui.label(format!("{:.1}", damage / elapsed));
+        ui.label(format!("{:.0}", overkill));
+        ui.end_row();
+      }
+    });
+    ui.separator();
+    egui::Grid::new("damage_meter_kills").striped(true).show(ui, |ui| {
+      ui.label("Enemy");
+      ui.label("Kills");
+      ui.end_row();
+      for (enemy, count) in kills.iter() {
+        ui.label(enemy.as_str());
+        ui.label(count.to_string());
+        ui.end_row();
+      }
+    });
+  });
+}
+
+// written whenever a floor is left, so spell damage can be balanced against enemy hp
+pub fn export_combat_log(log: Res<CombatLog>, dungeon: Res<Dungeon>) {
+  if log.entries.is_empty() {
+    return;
+  }
+  let timestamp = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map_or(0, |duration| duration.as_secs());
+  let directory = Path::new("combat_logs");
+  let path = directory.join(format!("{}_floor_{}.csv", timestamp, dungeon.current + 1));
+  let result = fs::create_dir_all(directory).and_then(|_| fs::write(&path, log.to_csv()));
+  match result {
+    Ok(_) => info!("combat log written to {}", path.display()),
+    Err(err) => error!("could not write combat log: {:?}", err),
+  }
+}
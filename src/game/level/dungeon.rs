@@ -0,0 +1,135 @@
+use super::{
+  generator::{Level, Point, TileType, WallType},
+  player::PlayerComponent,
+  settings::LevelSettings,
+};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+
+#[derive(Component, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Stairs {
+  Up,
+  Down,
+}
+
+// floors that are not active are kept here so they can be restored when the player returns,
+// the active floor lives in the `Level` resource
+#[derive(Default)]
+pub struct Dungeon {
+  pub floors: Vec<Level>,
+  pub current: usize,
+  pub travelling: Option<Stairs>,
+  pub show_map: bool,
+}
+
+impl Dungeon {
+  // only reaching a floor for the first time completes a level
+  pub fn is_new_floor(&self, stairs: Stairs) -> bool {
+    stairs == Stairs::Down && self.current + 1 >= self.floors.len()
+  }
+
+  pub fn change_floor(&mut self, mut current: Level, generate: impl FnOnce() -> Level) -> Level {
+    let stairs = match self.travelling.take() {
+      Some(stairs) => stairs,
+      None => {
+        // not coming from another floor, start a new dungeon
+        self.floors = vec![Level::default()];
+        self.current = 0;
+        return generate();
+      }
+    };
+
+    // enemies are despawned with the floor, let the spawners spawn them again
+    for (_, _, tile) in current.get_tiles_mut() {
+      tile.spawned = false;
+    }
+    self.floors[self.current] = current;
+
+    self.current = match stairs {
+      Stairs::Up => self.current.saturating_sub(1),
+      Stairs::Down => self.current + 1,
+    };
+
+    let mut level = if self.current < self.floors.len() {
+      std::mem::take(&mut self.floors[self.current])
+    } else {
+      let mut level = generate();
+      level.add_entry_point();
+      self.floors.push(Level::default());
+      level
+    };
+
+    // arrive next to the stairs we came from
+    let stairs_point = match stairs {
+      Stairs::Up => level.exit_point,
+      Stairs::Down => level.entry_point.unwrap_or(level.player_start_position),
+    };
+    level.player_start_position = level.arrival_point(stairs_point).unwrap_or(stairs_point);
+    level
+  }
+}
+
+fn tile_at(translation: Vec3, tile_size: Vec2) -> Point {
+  Point {
+    x: (translation.x / tile_size.x) as i32,
+    y: (translation.y / tile_size.y) as i32,
+  }
+}
+
+pub fn explore_tiles(
+  mut level: ResMut<Level>,
+  settings: Res<LevelSettings<WallType, TileType>>,
+  qry: Query<&Transform, With<PlayerComponent>>,
+) {
+  if let Ok(transform) = qry.get_single() {
+    level.explore(tile_at(transform.translation, settings.tile_size), 8);
+  }
+}
+
+// F4 shows the explored part of the current floor
+pub fn toggle_map(keyboard_input: Res<Input<KeyCode>>, mut dungeon: ResMut<Dungeon>) {
+  if keyboard_input.just_pressed(KeyCode::F4) {
+    dungeon.show_map = !dungeon.show_map;
+  }
+}
+
+pub fn show_map(
+  dungeon: Res<Dungeon>,
+  level: Res<Level>,
+  settings: Res<LevelSettings<WallType, TileType>>,
+  qry: Query<&Transform, With<PlayerComponent>>,
+  mut egui_context: ResMut<EguiContext>,
+) {
+  if !dungeon.show_map {
+    return;
+  }
+  const CELL: f32 = 3.; // size of a tile on the map
+
+  egui::Window::new(format!("Floor {}", dungeon.current + 1))
+    .id(egui::Id::new("floor_map"))
+    .show(egui_context.ctx_mut(), |ui| {
+      let size = egui::Vec2::new(level.width as f32 * CELL, level.height as f32 * CELL);
+      let (response, painter) = ui.allocate_painter(size, egui::Sense::hover());
+      // the level grows upwards, egui downwards
+      let origin = response.rect.left_bottom();
+      let cell = |point: Point| {
+        egui::Rect::from_min_size(
+          egui::pos2(origin.x + point.x as f32 * CELL, origin.y - (point.y + 1) as f32 * CELL),
+          egui::Vec2::splat(CELL),
+        )
+      };
+
+      for (x, y, tile) in level.get_tiles().filter(|(_, _, tile)| tile.explored) {
+        let color = match tile.tile_type {
+          TileType::Nothing => continue,
+          TileType::Exit => egui::Color32::GOLD,
+          TileType::Dirt => egui::Color32::from_gray(110),
+        };
+        painter.rect_filled(cell(Point { x, y }), 0., color);
+      }
+      if let Ok(transform) = qry.get_single() {
+        let player = tile_at(transform.translation, settings.tile_size);
+        painter.rect_filled(cell(player), 0., egui::Color32::RED);
+      }
+    });
+}
@@ -1,4 +1,4 @@
-use crate::systems::AtlasAnimationDefinition;
+use crate::systems::{AtlasAnimationDefinition, DamageType, HitReaction, Resistances, SpellType};
 use bevy::{prelude::*, utils::HashMap};
 
 #[derive(Eq, PartialEq, Debug, Hash, Clone)]
@@ -14,7 +14,17 @@ pub struct EnemyDefinition {
   pub idle: AtlasAnimationDefinition,
   pub death: AtlasAnimationDefinition,
   pub max_hp: f32,
+  pub contact_damage: f32,
+  pub resistances: Resistances,
+  pub hit_reaction: HitReaction,
   pub fodder: bool,
+  pub attack: Option<EnemyAttack>, // the spell is taken from the spell library
+}
+
+#[derive(Clone)]
+pub struct EnemyAttack {
+  pub spell: SpellType,
+  pub range: f32, // start casting once the player is this close
 }
 
 pub struct EnemyDictionary {
@@ -43,7 +53,21 @@ impl FromWorld for EnemyDictionary {
       EnemyType::Slime,
       EnemyDefinition {
         max_hp: 10.,
+        contact_damage: 5.,
+        resistances: Resistances {
+          resistances: [
+            (DamageType::Poison, 1.),
+            (DamageType::Fire, -0.5),
+          ]
+          .into_iter()
+          .collect(),
+        },
+        hit_reaction: HitReaction {
+          mass: 1.,
+          stagger: 0.3,
+        },
         fodder: true,
+        attack: None,
         texture_atlas: texture_atlas_handle.clone(),
         idle: AtlasAnimationDefinition {
           start: 84,
@@ -67,7 +91,17 @@ impl FromWorld for EnemyDictionary {
       EnemyType::Goblin,
       EnemyDefinition {
         max_hp: 10.,
+        contact_damage: 10.,
+        resistances: Resistances::default(),
+        hit_reaction: HitReaction {
+          mass: 1.5,
+          stagger: 0.25,
+        },
         fodder: true,
+        attack: Some(EnemyAttack {
+          spell: SpellType::GoblinSwipe,
+          range: 25.,
+        }),
         texture_atlas: texture_atlas_handle.clone(),
         idle: AtlasAnimationDefinition {
           start: 28,
@@ -92,7 +126,24 @@ impl FromWorld for EnemyDictionary {
       EnemyType::Eye,
       EnemyDefinition {
         max_hp: 10.,
+        contact_damage: 5.,
+        resistances: Resistances {
+          resistances: [
+            (DamageType::Shadow, 0.5),
+            (DamageType::Lightning, -0.5),
+          ]
+          .into_iter()
+          .collect(),
+        },
+        hit_reaction: HitReaction {
+          mass: 0.8,
+          stagger: 0.3,
+        },
         fodder: true,
+        attack: Some(EnemyAttack {
+          spell: SpellType::EyeBolt,
+          range: 150.,
+        }),
         texture_atlas: texture_atlas_handle.clone(),
         idle: AtlasAnimationDefinition {
           start: 0,
@@ -120,7 +171,21 @@ impl FromWorld for EnemyDictionary {
       EnemyType::Boss,
       EnemyDefinition {
         max_hp: 10.,
+        contact_damage: 25.,
+        resistances: Resistances {
+          resistances: [
+            (DamageType::Physical, 0.25),
+            (DamageType::Shadow, 0.5),
+          ]
+          .into_iter()
+          .collect(),
+        },
+        hit_reaction: HitReaction {
+          mass: 10.,
+          stagger: 0.1,
+        },
         fodder: true,
+        attack: None,
         texture_atlas: texture_atlas_handle.clone(),
         idle: AtlasAnimationDefinition {
           start: 336,
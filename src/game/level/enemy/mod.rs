@@ -3,10 +3,13 @@ use super::{
   generator::{Level, Point},
   player::PlayerComponent,
 };
-use super::{LevelState, LevelTag};
-use crate::systems::Combatant;
+use super::{caster::Caster, LevelState, LevelTag};
+use crate::systems::{
+  CombatAction, Combatant, ContactDamage, Faction, SpellLibrary, Spellbook, StatusEffects,
+  Stunned,
+};
 use crate::systems::Movement;
-use crate::systems::{AtlasAnimation, CombatEvent, PhysicsLayers, TimedLife};
+use crate::systems::{AtlasAnimation, CombatEvent, GameRng, GameTime, PhysicsLayers, TimedLife};
 use bevy::math::Vec3Swizzles;
 use bevy::prelude::*;
 use heron::prelude::*;
@@ -33,18 +36,20 @@ impl Default for SpawnTimer {
 }
 
 pub fn spawn_enemies(
-  time: Res<Time>,
+  time: Res<GameTime>,
   mut commands: Commands,
   mut level: ResMut<Level>,
   mut timer: ResMut<SpawnTimer>,
   enemy_dict: Res<EnemyDictionary>,
+  spell_library: Res<SpellLibrary>,
+  mut rng: ResMut<GameRng>,
   qry: Query<&Transform, With<PlayerComponent>>,
 ) {
   timer.timer.tick(time.delta());
   if !timer.timer.just_finished() { return }
 
   if let Ok(player_transform) = qry.get_single() {
-    let mut rng = rand::thread_rng();
+    let rng = &mut rng.spawning;
     for (x, y, mut tile) in level.get_tiles_mut() {
       let pos = Vec2::new(x as f32, y as f32) * 16.0;
       if !tile.is_spawn_point
@@ -66,7 +71,17 @@ pub fn spawn_enemies(
 
       tile.spawned = true;
 
-      commands
+      let attack = def.attack.as_ref().and_then(|attack| {
+        match spell_library.spells.get(&attack.spell) {
+          Some(spell) => Some((attack, spell)),
+          None => {
+            warn!("{:?} needs {:?} in the spell library", enemy_type, attack.spell);
+            None
+          }
+        }
+      });
+
+      let entity = commands
         .spawn_bundle(SpriteSheetBundle {
           texture_atlas: def.texture_atlas.clone(),
           transform: Transform::from_translation(Vec3::from((pos, crate::z::ENEMY))),
@@ -76,6 +91,12 @@ pub fn spawn_enemies(
           hp: def.max_hp,
           hp_max: def.max_hp,
         })
+        .insert(def.resistances.clone())
+        .insert(def.hit_reaction.clone())
+        .insert(ContactDamage {
+          damage: def.contact_damage,
+          invulnerability: 0.5,
+        })
         .insert(LevelTag)
         .insert(def.idle.clone())
         .insert(AtlasAnimation::default())
@@ -108,7 +129,20 @@ pub fn spawn_enemies(
           target: None,
           ..Default::default()
         })
-        .insert(Velocity::from(Vec3::splat(0.0)));
+        .insert(Velocity::from(Vec3::splat(0.0)))
+        .insert(Spellbook {
+          spells: attack
+            .iter()
+            .map(|(attack, spell)| (attack.spell, (*spell).clone()))
+            .collect(),
+          ..Default::default()
+        })
+        .insert(Faction::Monsters)
+        .id();
+
+      if let Some((attack, _)) = attack {
+        commands.entity(entity).insert(Caster::new(attack.spell, attack.range));
+      }
     }
   }
 }
@@ -120,13 +154,28 @@ fn despawn_dead(
   enemy_dic: Res<EnemyDictionary>,
   qry: Query<(&Enemy, &Transform, &Velocity)>,
   qry_killer: Query<&Transform>,
+  mut qry_tinted: Query<(&StatusEffects, &mut TextureAtlasSprite)>,
   mut evts: EventReader<CombatEvent>,
 ) {
   for evt in evts.iter() {
     match evt {
       CombatEvent::CombatantKilled(victim_entity, killer_entity) => {
+        if let Ok((status_effects, mut sprite)) = qry_tinted.get_mut(*victim_entity) {
+          if let Some(base_color) = status_effects.base_color {
+            sprite.color = base_color;
+          }
+        }
+        commands
+          .entity(*victim_entity)
+          .remove::<StatusEffects>()
+          .remove::<Stunned>();
+
+        // not every combatant is an enemy (e.g. breakable walls)
+        let (enemy, transform, v) = match qry.get(*victim_entity) {
+          Ok(result) => result,
+          _ => continue,
+        };
         stats.kills += 1;
-        let (enemy, transform, v) = qry.get(*victim_entity).expect("Enemy not found");
         let mut spawner = level.get_tile_mut(enemy.spawn_point.x, enemy.spawn_point.y);
         let def = enemy_dic
           .enemies
@@ -171,6 +220,23 @@ fn despawn_dead(
   }
 }
 
+// enemies only ever attack the player
+fn enemy_attack(
+  qry_player: Query<&Transform, With<PlayerComponent>>,
+  mut qry: Query<(Entity, &mut Caster, &Spellbook, &mut Movement, &Transform), Without<Stunned>>,
+  mut evts: EventWriter<CombatAction>,
+) {
+  let player_pos = match qry_player.get_single() {
+    Ok(transform) => transform.translation.xy(),
+    _ => return,
+  };
+
+  for (entity, mut caster, spellbook, mut mov, transform) in qry.iter_mut() {
+    let position = transform.translation.xy();
+    caster.try_cast(entity, position, player_pos, spellbook, &mut mov, &mut evts);
+  }
+}
+
 fn chase_player(
   qry_player: Query<(&PlayerComponent, &Transform), Changed<Transform>>,
   mut qry: Query<(&Enemy, &mut Movement, &Transform)>,
@@ -196,6 +262,7 @@ impl Plugin for EnemyPlugin {
       SystemSet::on_update(LevelState::Loaded)
         .with_system(despawn_dead)
         .with_system(chase_player)
+        .with_system(enemy_attack.after("advance_casters"))
         .with_system(spawn_enemies),
     );
   }
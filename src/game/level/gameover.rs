@@ -0,0 +1,77 @@
+use super::ui::Stats;
+use crate::GameState;
+use bevy::prelude::*;
+
+#[derive(Component)]
+pub struct GameOverTag;
+
+pub fn show_game_over(mut commands: Commands, asset_server: Res<AssetServer>, stats: Res<Stats>) {
+  let font = asset_server.load("Shizuru-Regular.ttf");
+  let text_style = TextStyle {
+    font: font.clone(),
+    font_size: 40.0,
+    color: Color::rgb(0.9, 0.9, 0.9),
+  };
+
+  commands
+    .spawn_bundle(NodeBundle {
+      style: Style {
+        margin: Rect::all(Val::Auto),
+        flex_direction: FlexDirection::ColumnReverse,
+        align_items: AlignItems::Center,
+        ..Default::default()
+      },
+      color: Color::CRIMSON.into(),
+      ..Default::default()
+    })
+    .insert(GameOverTag)
+    .with_children(|parent| {
+      parent.spawn_bundle(TextBundle {
+        style: Style {
+          margin: Rect::all(Val::Px(50.0)),
+          ..Default::default()
+        },
+        text: Text::with_section(
+          "You died",
+          TextStyle {
+            font: font.clone(),
+            font_size: 80.0,
+            color: Color::rgb(0.9, 0.9, 0.9),
+          },
+          Default::default(),
+        ),
+        ..Default::default()
+      });
+
+      // run summary
+      for line in [
+        format!("{} levels completed", stats.levels_completed),
+        format!("{} kills", stats.kills),
+        format!("{:.0}s survived", stats.time_elapsed),
+        "Press any key to return to the menu".to_owned(),
+      ] {
+        parent.spawn_bundle(TextBundle {
+          style: Style {
+            margin: Rect::all(Val::Px(10.0)),
+            ..Default::default()
+          },
+          text: Text::with_section(line, text_style.clone(), Default::default()),
+          ..Default::default()
+        });
+      }
+    });
+}
+
+pub fn return_to_menu(
+  keyboard_input: Res<Input<KeyCode>>,
+  mouse_button_input: Res<Input<MouseButton>>,
+  mut game_state: ResMut<State<GameState>>,
+) {
+  if keyboard_input.get_just_pressed().next().is_some()
+    || mouse_button_input.get_just_pressed().next().is_some()
+  {
+    game_state
+      .set(GameState::Menu)
+      .expect("set game state should always succeed");
+  }
+}
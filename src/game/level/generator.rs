@@ -47,9 +47,11 @@ pub struct Level {
   pub height: u32,
   pub tiles: Vec<Vec<LevelTile>>, // quadtrees?
   pub rooms: Vec<Rect>,
+  pub secret_rooms: Vec<SecretRoom>,
   pub collission_shapes: Vec<Rect>,
   pub player_start_position: Point,
-  pub exit_point: Point,
+  pub exit_point: Point,          // stairs down
+  pub entry_point: Option<Point>, // stairs up, the first floor has none
   // pub tile_size: Vec2
 }
 
@@ -60,6 +62,15 @@ pub struct LevelTile {
   pub wall_type: WallType,
   pub is_spawn_point: bool,
   pub spawned: bool,
+  pub explored: bool,
+}
+
+// optional room hidden behind a breakable wall, its tiles stay empty until revealed
+#[derive(Clone, Debug)]
+pub struct SecretRoom {
+  pub room: Rect,
+  pub wall: Rect,
+  pub revealed: bool,
 }
 
 impl Level {
@@ -68,6 +79,7 @@ impl Level {
     let mut rng: StdRng = SeedableRng::from_seed(seed);
     retval.place_rooms(&mut rng);
     retval.place_corridors(&mut rng);
+    retval.place_secret_rooms(&mut rng);
     retval.calculate_walls();
     retval.calculate_collission_shapes();
     retval.calculate_spawn_points(&mut rng);
@@ -87,9 +99,8 @@ impl Level {
     Self::generate_seed_bytes(seed_bytes, width, height)
   }
 
-  pub fn generate_random(width: u32, height: u32) -> Self {
+  pub fn generate_random(rng: &mut impl Rng, width: u32, height: u32) -> Self {
     let mut seed = [0u8; 32];
-    let mut rng = rand::thread_rng();
     rng.fill_bytes(&mut seed);
 
     Self::generate_seed_bytes(seed, width, height)
@@ -237,9 +248,138 @@ impl Level {
     }
   }
 
+  pub fn place_secret_rooms(&mut self, rng: &mut StdRng) {
+    let max_secret_rooms = 3;
+    let max_attempts = 20;
+    let min_room_size = 5;
+    let max_room_size = 8;
+    let wall_thickness = 2;
+
+    for _ in 0..max_attempts {
+      if self.secret_rooms.len() >= max_secret_rooms || self.rooms.is_empty() {
+        break;
+      }
+
+      let parent = self.rooms[rng.gen_range(0..self.rooms.len())].clone();
+      let width = rng.gen_range(min_room_size..=max_room_size);
+      let height = rng.gen_range(min_room_size..=max_room_size);
+      let (cx, cy) = (parent.centre.x, parent.centre.y);
+
+      let (wall, room) = match rng.gen_range(0..4) {
+        0 => (
+          Rect::new(parent.x2, cy - 1, wall_thickness, 3),
+          Rect::new(parent.x2 + wall_thickness, cy - height / 2, width, height),
+        ),
+        1 => (
+          Rect::new(parent.x - wall_thickness, cy - 1, wall_thickness, 3),
+          Rect::new(parent.x - wall_thickness - width, cy - height / 2, width, height),
+        ),
+        2 => (
+          Rect::new(cx - 1, parent.y2, 3, wall_thickness),
+          Rect::new(cx - width / 2, parent.y2 + wall_thickness, width, height),
+        ),
+        _ => (
+          Rect::new(cx - 1, parent.y - wall_thickness, 3, wall_thickness),
+          Rect::new(cx - width / 2, parent.y - wall_thickness - height, width, height),
+        ),
+      };
+
+      // keep a border of empty tiles around the room so it stays hidden
+      let bounds = Rect::new(room.x - 1, room.y - 1, room.width + 2, room.height + 2);
+      if bounds.x < 0
+        || bounds.y < 0
+        || bounds.x2 > self.width as i32
+        || bounds.y2 > self.height as i32
+        || !self.is_empty(&bounds)
+        || !self.is_empty(&wall)
+        || self
+          .secret_rooms
+          .iter()
+          .any(|other| bounds.intersects(&other.room) || bounds.intersects(&other.wall))
+      {
+        continue;
+      }
+
+      self.secret_rooms.push(SecretRoom {
+        room,
+        wall,
+        revealed: false,
+      });
+    }
+  }
+
+  // turns the secret room and its wall into floor, returns the tiles that changed
+  pub fn reveal_secret_room(&mut self, index: usize) -> Option<Rect> {
+    let secret = self.secret_rooms.get_mut(index)?;
+    if secret.revealed {
+      return None;
+    }
+    secret.revealed = true;
+    let (room, wall) = (secret.room.clone(), secret.wall.clone());
+
+    for rect in [&room, &wall] {
+      for x in rect.x..rect.x2 {
+        for y in rect.y..rect.y2 {
+          self.set(x, y, TileType::Dirt);
+        }
+      }
+    }
+
+    // walls depend on the neighbouring tiles, so grow the dirty region by one
+    let union = room.union(&wall);
+    let x = max(union.x - 1, 0);
+    let y = max(union.y - 1, 0);
+    let region = Rect::new(
+      x,
+      y,
+      min(union.x2 + 1, self.width as i32) - x,
+      min(union.y2 + 1, self.height as i32) - y,
+    );
+
+    self.calculate_walls_in(&region);
+    self.update_collission_shapes(&region);
+    Some(region)
+  }
+
+  pub fn add_entry_point(&mut self) {
+    let entry = self.player_start_position;
+    self.entry_point = Some(entry);
+    self.set(entry.x, entry.y, TileType::Exit);
+  }
+
+  // closest open floor tile that is far enough from the stairs at `point` not to touch them
+  pub fn arrival_point(&self, point: Point) -> Option<Point> {
+    self
+      .get_tiles()
+      .filter(|(x, y, tile)| {
+        tile.tile_type == TileType::Dirt
+          && tile.wall_type == WallType::Nothing
+          && max(absdiff(*x, point.x), absdiff(*y, point.y)) >= 2
+      })
+      .min_by_key(|(x, y, _)| (x - point.x).pow(2) + (y - point.y).pow(2))
+      .map(|(x, y, _)| Point { x, y })
+  }
+
+  pub fn explore(&mut self, centre: Point, radius: i32) {
+    for x in max(centre.x - radius, 0)..min(centre.x + radius + 1, self.width as i32) {
+      for y in max(centre.y - radius, 0)..min(centre.y + radius + 1, self.height as i32) {
+        self.tiles[x as usize][y as usize].explored = true;
+      }
+    }
+  }
+
+  fn is_empty(&self, rect: &Rect) -> bool {
+    (rect.x..rect.x2).all(|x| (rect.y..rect.y2).all(|y| self.get(x, y) == TileType::Nothing))
+  }
+
   fn calculate_walls(&mut self) {
-    for x in 0..(self.width as i32) {
-      for y in 0..(self.height as i32) {
+    self.calculate_walls_in(&Rect::new(0, 0, self.width as i32, self.height as i32));
+  }
+
+  fn calculate_walls_in(&mut self, region: &Rect) {
+    for x in region.x..region.x2 {
+      for y in region.y..region.y2 {
+        self.set_wall(x, y, WallType::Nothing);
         if self.get(x, y) == TileType::Nothing {
           if self.get(x, y - 1) == TileType::Dirt {
             if self.get(x - 1, y - 1) == TileType::Nothing {
@@ -316,35 +456,84 @@ impl Level {
   }
 
   fn calculate_collission_shapes(&mut self) {
-    let mut rects = self
+    let region = Rect::new(0, 0, self.width as i32, self.height as i32);
+    let grid = self.solid_sub_tiles(&region);
+    let mut covered = vec![vec![false; self.height as usize * 3]; self.width as usize * 3];
+    self.collission_shapes = merge_rects(&grid, &mut covered, &region.scale(3));
+  }
+
+  // only re-meshes the shapes touching the region, everything else is left as is
+  fn update_collission_shapes(&mut self, region: &Rect) {
+    let sub_region = region.scale(3);
+    let (removed, kept): (Vec<_>, Vec<_>) = self
+      .collission_shapes
+      .drain(..)
+      .partition(|rect| rect.intersects(&sub_region));
+
+    let bounds = removed
+      .iter()
+      .fold(sub_region, |bounds, rect| bounds.union(rect));
+    let tile_bounds = Rect::new(
+      bounds.x / 3,
+      bounds.y / 3,
+      (bounds.x2 + 2) / 3 - bounds.x / 3,
+      (bounds.y2 + 2) / 3 - bounds.y / 3,
+    );
+    let grid = self.solid_sub_tiles(&tile_bounds);
+
+    let mut covered = vec![vec![false; self.height as usize * 3]; self.width as usize * 3];
+    for rect in kept.iter().filter(|rect| rect.intersects(&bounds)) {
+      for x in rect.x..rect.x2 {
+        for y in rect.y..rect.y2 {
+          covered[x as usize][y as usize] = true;
+        }
+      }
+    }
+
+    self.collission_shapes = kept;
+    self
+      .collission_shapes
+      .extend(merge_rects(&grid, &mut covered, &bounds));
+  }
+
+  // each tile is split into a 3x3 grid so walls can be thinner than a full tile
+  fn solid_sub_tiles(&self, region: &Rect) -> Vec<Vec<bool>> {
+    let mut grid = vec![vec![false; self.height as usize * 3]; self.width as usize * 3];
+
+    let rects = self
       .get_tiles()
+      .filter(|(x, y, _)| {
+        *x >= region.x && *x < region.x2 && *y >= region.y && *y < region.y2
+      })
       .map(|(x, y, t)| (x, y, t.tile_type.clone(), t.wall_type.clone()))
       .flat_map(|(x, y, t, w)| match (t, w) {
-        (TileType::Nothing, _) => vec![Some(Rect::new(x * 3, y * 3, 3, 3))],
+        (TileType::Nothing, _) => vec![Rect::new(x * 3, y * 3, 3, 3)],
         (_, WallType::West) | (_, WallType::WestInnerCorner) => {
-          vec![Some(Rect::new(x * 3, y * 3, 1, 3))]
+          vec![Rect::new(x * 3, y * 3, 1, 3)]
         }
         (_, WallType::East) | (_, WallType::EastInnerCorner) => {
-          vec![Some(Rect::new(x * 3 + 2, y * 3, 1, 3))]
+          vec![Rect::new(x * 3 + 2, y * 3, 1, 3)]
         }
-        (_, WallType::South) => vec![Some(Rect::new(x * 3, y * 3, 3, 1))],
+        (_, WallType::South) => vec![Rect::new(x * 3, y * 3, 3, 1)],
         (_, WallType::Southeast) => vec![
-          Some(Rect::new(x * 3, y * 3, 3, 1)),
-          Some(Rect::new(x * 3 + 2, y * 3 + 1, 1, 2)),
+          Rect::new(x * 3, y * 3, 3, 1),
+          Rect::new(x * 3 + 2, y * 3 + 1, 1, 2),
         ],
         (_, WallType::Southwest) => vec![
-          Some(Rect::new(x * 3, y * 3, 3, 1)),
-          Some(Rect::new(x * 3, y * 3 + 1, 1, 2)),
+          Rect::new(x * 3, y * 3, 3, 1),
+          Rect::new(x * 3, y * 3 + 1, 1, 2),
         ],
-        _ => vec![None],
-      })
-      .filter_map(|i| i)
-      .collect::<Vec<_>>();
+        _ => vec![],
+      });
 
-    for _ in 0..10 {
-      merge_rects(&mut rects);
+    for rect in rects {
+      for x in rect.x..rect.x2 {
+        for y in rect.y..rect.y2 {
+          grid[x as usize][y as usize] = true;
+        }
+      }
     }
-    self.collission_shapes = rects;
+    grid
   }
 
   fn calculate_spawn_points(&mut self, rng: &mut StdRng) {
@@ -447,24 +636,47 @@ impl Level {
   // }
 }
 
-fn merge_rects(rects: &mut Vec<Rect>) {
-  for i in 0..rects.len() {
-    if rects[i].merged {
-      continue;
-    }
-    for j in i + 1..rects.len() {
-      if rects[j].merged {
+// greedy meshing: grow each uncovered cell as far right as possible, then as far up as the whole
+// span allows. converges in a single pass and the rects never overlap.
+fn merge_rects(grid: &Vec<Vec<bool>>, covered: &mut Vec<Vec<bool>>, bounds: &Rect) -> Vec<Rect> {
+  let width = grid.len() as i32;
+  let height = grid.first().map_or(0, |col| col.len()) as i32;
+  let (x1, x2) = (max(bounds.x, 0) as usize, min(bounds.x2, width) as usize);
+  let (y1, y2) = (max(bounds.y, 0) as usize, min(bounds.y2, height) as usize);
+  let is_free = |covered: &Vec<Vec<bool>>, x: usize, y: usize| grid[x][y] && !covered[x][y];
+  let mut rects = Vec::new();
+
+  for y in y1..y2 {
+    for x in x1..x2 {
+      if !is_free(covered, x, y) {
         continue;
       }
-      if rects[i].can_merge(&rects[j]) {
-        rects[j].merged = true;
-        let other = rects[j].clone();
-        rects[i].merge(&other);
-        break;
+
+      let mut right = x + 1;
+      while right < x2 && is_free(covered, right, y) {
+        right += 1;
+      }
+
+      let mut top = y + 1;
+      while top < y2 && (x..right).all(|col| is_free(covered, col, top)) {
+        top += 1;
+      }
+
+      for col in covered.iter_mut().take(right).skip(x) {
+        for cell in col.iter_mut().take(top).skip(y) {
+          *cell = true;
+        }
       }
+
+      rects.push(Rect::new(
+        x as i32,
+        y as i32,
+        (right - x) as i32,
+        (top - y) as i32,
+      ));
     }
   }
-  rects.retain(|r| !r.merged);
+  rects
 }
 
 #[derive(Default, Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
@@ -479,7 +691,7 @@ impl Point {
   }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Rect {
   pub x: i32,
   pub y: i32,
@@ -488,7 +700,6 @@ pub struct Rect {
   pub width: i32,
   pub height: i32,
   pub centre: Point,
-  pub merged: bool,
 }
 
 impl Rect {
@@ -504,7 +715,6 @@ impl Rect {
         x: x + (width / 2),
         y: y + (height / 2),
       },
-      merged: false,
     }
   }
 
@@ -512,29 +722,81 @@ impl Rect {
     self.x <= other.x2 && self.x2 >= other.x && self.y <= other.y2 && self.y2 >= other.y
   }
 
-  pub fn can_merge(&self, other: &Self) -> bool {
-    (self.x == other.x && self.x2 == other.x2 && (self.y == other.y2 || self.y2 == other.y))
-      || (self.y == other.y && self.y2 == other.y2 && (self.x == other.x2 || self.x2 == other.x))
+  pub fn union(&self, other: &Self) -> Self {
+    let x = min(self.x, other.x);
+    let y = min(self.y, other.y);
+    Rect::new(x, y, max(self.x2, other.x2) - x, max(self.y2, other.y2) - y)
   }
-  pub fn merge(&mut self, other: &Self) {
-    if self.x == other.x && self.x2 == other.x2 {
-      self.y = min(self.y, other.y);
-      self.y2 = max(self.y2, other.y2);
-    } else if self.y == other.y && self.y2 == other.y2 {
-      self.x = min(self.x, other.x);
-      self.x2 = max(self.x2, other.x2);
-    } else {
-      panic!("cannot merge rects");
-    }
-    self.width = self.x2 - self.x;
-    self.height = self.y2 - self.y;
-    self.centre = Point {
-      x: self.x + (self.width / 2),
-      y: self.y + (self.height / 2),
-    };
+  pub fn scale(&self, factor: i32) -> Self {
+    Rect::new(
+      self.x * factor,
+      self.y * factor,
+      self.width * factor,
+      self.height * factor,
+    )
   }
   #[inline]
   pub fn area(&self) -> i32 {
     self.width * self.height
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::collections::HashSet;
+
+  // every sub-tile covered by the rects, fails if two rects share one
+  fn covered_cells(rects: &[Rect]) -> HashSet<(i32, i32)> {
+    let mut cells = HashSet::new();
+    for rect in rects {
+      for x in rect.x..rect.x2 {
+        for y in rect.y..rect.y2 {
+          assert!(cells.insert((x, y)), "rects overlap at {:?}", (x, y));
+        }
+      }
+    }
+    cells
+  }
+
+  fn solid_cells(level: &Level) -> HashSet<(i32, i32)> {
+    let region = Rect::new(0, 0, level.width as i32, level.height as i32);
+    let grid = level.solid_sub_tiles(&region);
+    let mut cells = HashSet::new();
+    for (x, col) in grid.iter().enumerate() {
+      for (y, solid) in col.iter().enumerate() {
+        if *solid {
+          cells.insert((x as i32, y as i32));
+        }
+      }
+    }
+    cells
+  }
+
+  #[test]
+  fn collission_shapes_cover_exactly_the_solid_sub_tiles() {
+    for seed in 0..8u8 {
+      let level = Level::generate_seed_bytes([seed; 32], 64, 64);
+      assert_eq!(covered_cells(&level.collission_shapes), solid_cells(&level));
+    }
+  }
+
+  #[test]
+  fn partial_remesh_matches_full_remesh() {
+    let mut revealed = 0;
+    for seed in 0..8u8 {
+      let mut level = Level::generate_seed_bytes([seed; 32], 64, 64);
+      for index in 0..level.secret_rooms.len() {
+        level.reveal_secret_room(index);
+        revealed += 1;
+
+        let partial = covered_cells(&level.collission_shapes);
+        let mut full = level.clone();
+        full.calculate_collission_shapes();
+        assert_eq!(partial, covered_cells(&full.collission_shapes));
+        assert_eq!(partial, solid_cells(&level));
+      }
+    }
+    assert!(revealed > 0, "no secret rooms were generated");
+  }
+}
@@ -0,0 +1,108 @@
+use super::{
+  camera::CameraShake,
+  enemy::{Enemy, EnemyType},
+  player::PlayerComponent,
+};
+use crate::systems::{CombatEvent, Combatant, GameTime};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+
+// how strongly hits are felt, 1 is the full effect and 0 turns it off
+pub struct ImpactSettings {
+  pub hitstop: f32,
+  pub camera_shake: f32,
+  pub show_settings: bool,
+}
+impl Default for ImpactSettings {
+  fn default() -> Self {
+    ImpactSettings {
+      hitstop: 1.,
+      camera_shake: 1.,
+      show_settings: false,
+    }
+  }
+}
+
+pub fn impact_feedback(
+  settings: Res<ImpactSettings>,
+  mut game_time: ResMut<GameTime>,
+  mut events: EventReader<CombatEvent>,
+  qry_player: Query<Entity, With<PlayerComponent>>,
+  qry_enemies: Query<&Enemy>,
+  qry_combatants: Query<&Combatant>,
+  mut qry_camera: Query<&mut CameraShake>,
+) {
+  let player = match qry_player.get_single() {
+    Ok(player) => player,
+    _ => return,
+  };
+
+  let mut hitstop: f32 = 0.;
+  let mut trauma = 0.;
+  for evt in events.iter() {
+    let damage = match evt {
+      CombatEvent::DamageApplied(damage) => damage,
+      _ => continue,
+    };
+    // only fights the player is part of, hits by minions are credited to the player already
+    let player_hit = damage.victim == player;
+    if (damage.source != player && !player_hit) || damage.amount <= 0. {
+      continue;
+    }
+    let boss = qry_enemies
+      .get(damage.victim)
+      .map_or(false, |enemy| enemy.enemy_type == EnemyType::Boss);
+    let hp_max = qry_combatants.get(damage.victim).map_or(1., |c| c.hp_max.max(1.));
+
+    // the bigger the chunk of health, the harder the shake
+    trauma += (damage.amount / hp_max).min(1.) * 0.3;
+    if damage.crit {
+      hitstop = hitstop.max(0.05);
+      trauma += 0.2;
+    }
+    if damage.fatal {
+      hitstop = hitstop.max(0.08);
+      trauma += 0.3;
+    }
+    if boss {
+      hitstop = hitstop.max(0.1);
+      trauma += 0.3;
+    }
+    if player_hit {
+      trauma += 0.3;
+    }
+  }
+
+  if hitstop > 0. && settings.hitstop > 0. {
+    game_time.freeze(hitstop * settings.hitstop);
+  }
+  if trauma > 0. && settings.camera_shake > 0. {
+    for mut shake in qry_camera.iter_mut() {
+      shake.add_trauma(trauma * settings.camera_shake);
+    }
+  }
+}
+
+// F3 shows the hitstop and camera shake settings
+pub fn toggle_impact_settings(
+  keyboard_input: Res<Input<KeyCode>>,
+  mut settings: ResMut<ImpactSettings>,
+) {
+  if keyboard_input.just_pressed(KeyCode::F3) {
+    settings.show_settings = !settings.show_settings;
+  }
+}
+
+pub fn show_impact_settings(
+  mut settings: ResMut<ImpactSettings>,
+  mut egui_context: ResMut<EguiContext>,
+) {
+  if !settings.show_settings {
+    return;
+  }
+  let settings = &mut *settings;
+  egui::Window::new("Accessibility").show(egui_context.ctx_mut(), |ui| {
+    ui.add(egui::Slider::new(&mut settings.hitstop, 0.0..=1.0).text("Hitstop"));
+    ui.add(egui::Slider::new(&mut settings.camera_shake, 0.0..=1.0).text("Camera shake"));
+  });
+}
@@ -0,0 +1,173 @@
+use super::{
+  caster::{Caster, CasterState},
+  enemy::Enemy,
+  LevelState, LevelTag,
+};
+use crate::systems::{
+  AtlasAnimation, CombatAction, CombatEvent, Combatant, Faction, Movement, Owner, PhysicsLayers,
+  SpellLibrary, Spellbook, Stunned, TimedLife,
+};
+use bevy::{math::Vec3Swizzles, prelude::*};
+use heron::prelude::*;
+use std::f32::consts::TAU;
+
+const AGGRO_RANGE: f32 = 150.; // minions go after enemies this close to them
+const FOLLOW_DISTANCE: f32 = 30.; // without a target they stay this close to their owner
+
+#[derive(Component)]
+pub struct Minion;
+
+fn summon_minions(
+  mut commands: Commands,
+  spell_library: Res<SpellLibrary>,
+  mut actions: EventReader<CombatAction>,
+  qry: Query<(&Spellbook, &Transform, Option<&Owner>)>,
+) {
+  for action in actions.iter() {
+    let (entity, spell_type) = match action {
+      CombatAction::CastSpell(entity, spell_type, _) => (*entity, spell_type),
+      _ => continue,
+    };
+    let (spellbook, transform, owner) = match qry.get(entity) {
+      Ok(result) => result,
+      _ => continue,
+    };
+    let summon = match spellbook.spells.get(spell_type).and_then(|spell| spell.summon.as_ref()) {
+      Some(summon) => summon,
+      None => continue,
+    };
+    let attack = match spell_library.spells.get(&summon.attack) {
+      Some(attack) => attack,
+      None => {
+        warn!("summoned minions need {:?} in the spell library", summon.attack);
+        continue;
+      }
+    };
+    // minions summoned by minions still belong to the original owner
+    let owner = owner.map_or(entity, |owner| owner.entity);
+
+    for index in 0..summon.count {
+      let angle = TAU * index as f32 / summon.count as f32;
+      let position = transform.translation.xy() + Vec2::new(angle.cos(), angle.sin()) * 16.;
+      commands
+        .spawn_bundle(SpriteSheetBundle {
+          texture_atlas: summon.texture_atlas.clone(),
+          // tinted so they can't be mistaken for enemies
+          sprite: TextureAtlasSprite {
+            color: Color::rgb(0.6, 0.8, 1.),
+            ..Default::default()
+          },
+          transform: Transform::from_translation(Vec3::from((position, crate::z::ENEMY))),
+          ..Default::default()
+        })
+        .insert(Combatant {
+          hp: summon.max_hp,
+          hp_max: summon.max_hp,
+        })
+        .insert(LevelTag)
+        .insert(summon.idle.clone())
+        .insert(AtlasAnimation::default())
+        .insert(TimedLife::from_seconds(summon.lifetime))
+        .insert(Minion)
+        .insert(Caster::new(summon.attack, summon.range))
+        .insert(Owner { entity: owner })
+        .insert(Faction::Ally)
+        // physics
+        .insert(RigidBody::Dynamic)
+        .insert(CollisionShape::Sphere { radius: 7. })
+        .insert(PhysicMaterial {
+          friction: 0.2,
+          restitution: 1.0,
+          density: 1.0,
+          ..Default::default()
+        })
+        .insert(RotationConstraints::lock())
+        .insert(Damping::from_linear(3.0))
+        .insert(
+          CollisionLayers::none()
+            .with_group(PhysicsLayers::Player)
+            .with_mask(PhysicsLayers::Attacks)
+            .with_mask(PhysicsLayers::Enemies)
+            .with_mask(PhysicsLayers::World),
+        )
+        .insert(Movement {
+          speed: summon.speed,
+          enabled: true,
+          target: None,
+          ..Default::default()
+        })
+        .insert(Velocity::from(Vec3::splat(0.0)))
+        .insert(Spellbook {
+          spells: [(summon.attack, attack.clone())].into_iter().collect(),
+          ..Default::default()
+        });
+    }
+  }
+}
+
+// minions go after the closest enemy and otherwise stay close to their owner
+fn command_minions(
+  qry_enemies: Query<&Transform, With<Enemy>>,
+  qry_owners: Query<&Transform>,
+  mut qry: Query<
+    (Entity, &mut Caster, &Owner, &Spellbook, &mut Movement, &Transform),
+    (With<Minion>, Without<Stunned>),
+  >,
+  mut evts: EventWriter<CombatAction>,
+) {
+  for (entity, mut caster, owner, spellbook, mut mov, transform) in qry.iter_mut() {
+    if caster.state != CasterState::Idle {
+      continue;
+    }
+    let position = transform.translation.xy();
+    let target = qry_enemies
+      .iter()
+      .map(|enemy| enemy.translation.xy())
+      .filter(|enemy| enemy.distance(position) <= AGGRO_RANGE)
+      .min_by(|a, b| {
+        a.distance(position)
+          .partial_cmp(&b.distance(position))
+          .unwrap_or(std::cmp::Ordering::Equal)
+      });
+    match target {
+      Some(target) if caster.try_cast(entity, position, target, spellbook, &mut mov, &mut evts) => {
+        mov.target = None;
+      }
+      Some(target) => mov.target = Some(target),
+      None => {
+        mov.target = match qry_owners.get(owner.entity) {
+          Ok(owner) if owner.translation.xy().distance(position) > FOLLOW_DISTANCE => {
+            Some(owner.translation.xy())
+          }
+          _ => None,
+        };
+      }
+    }
+  }
+}
+
+fn despawn_dead_minions(
+  mut commands: Commands,
+  qry: Query<&Minion>,
+  mut evts: EventReader<CombatEvent>,
+) {
+  for evt in evts.iter() {
+    if let CombatEvent::CombatantKilled(victim, _) = evt {
+      if qry.get(*victim).is_ok() {
+        commands.entity(*victim).despawn_recursive();
+      }
+    }
+  }
+}
+
+pub struct MinionPlugin;
+impl Plugin for MinionPlugin {
+  fn build(&self, app: &mut App) {
+    app.add_system_set(
+      SystemSet::on_update(LevelState::Loaded)
+        .with_system(summon_minions)
+        .with_system(command_minions.after("advance_casters"))
+        .with_system(despawn_dead_minions),
+    );
+  }
+}
@@ -8,11 +8,18 @@ use settings::LevelSettings;
 use systems::*;
 
 pub mod camera;
+pub mod caster;
+pub mod combat_log;
 pub mod complete;
+pub mod dungeon;
 pub mod enemy;
+pub mod gameover;
 pub mod generator;
+pub mod impact;
 pub mod loading;
+pub mod minion;
 pub mod player;
+pub mod secrets;
 pub mod settings;
 pub mod systems;
 pub mod ui;
@@ -25,6 +32,7 @@ pub enum LevelState {
   Loaded,
   LevelComplete,
   BossComplete,
+  GameOver,
 }
 #[derive(Component)]
 pub struct LevelLoader {
@@ -40,10 +48,15 @@ impl Plugin for LevelPlugin {
     app
       .add_plugin(TilemapPlugin)
       .add_plugin(player::PlayerPlugin)
+      .add_plugin(caster::CasterPlugin)
       .add_plugin(enemy::EnemyPlugin)
+      .add_plugin(minion::MinionPlugin)
       .add_state(LevelState::Disabled)
       .init_resource::<generator::Level>()
+      .init_resource::<dungeon::Dungeon>()
       .init_resource::<ui::Stats>()
+      .init_resource::<combat_log::CombatLog>()
+      .init_resource::<impact::ImpactSettings>()
       .init_resource::<complete::CompletedLevels>()
       .init_resource::<LevelSettings<WallType, TileType>>()
       .add_system(crate::systems::set_texture_filters_to_nearest)
@@ -63,14 +76,32 @@ impl Plugin for LevelPlugin {
       // loaded
       .add_system_set(SystemSet::on_enter(LevelState::Loaded)
         .with_system(camera::setup_camera)
-        .with_system(ui::create_ui))
+        .with_system(ui::create_ui)
+        .with_system(ui::create_spell_bar)
+        .with_system(combat_log::reset_combat_log))
       .add_system_set(
         SystemSet::on_update(LevelState::Loaded)
           .with_system(check_level_complete)
+          .with_system(secrets::break_walls)
+          .with_system(dungeon::explore_tiles)
+          .with_system(dungeon::toggle_map)
+          .with_system(dungeon::show_map)
           .with_system(camera::camera_system)
           .with_system(ui::measure_time)
+          .with_system(ui::update_mana_bar)
+          .with_system(ui::update_spell_bar)
+          .with_system(combat_log::record_combat_log)
+          .with_system(combat_log::toggle_damage_meter)
+          .with_system(combat_log::show_damage_meter)
+          .with_system(impact::impact_feedback)
+          .with_system(impact::toggle_impact_settings)
+          .with_system(impact::show_impact_settings)
+          .with_system(camera::shake_camera)
           .with_system(camera::camera_system_initial_focus),
       )
+      .add_system_set(
+        SystemSet::on_exit(LevelState::Loaded).with_system(combat_log::export_combat_log),
+      )
       // level complete
       .add_system_set(
         SystemSet::on_enter(LevelState::LevelComplete)
@@ -88,11 +119,25 @@ impl Plugin for LevelPlugin {
         SystemSet::on_exit(LevelState::LevelComplete)
           .with_system(cleanup_system::<complete::CompleteLoadingTag>),
       )
+      // game over
+      .add_system_set(
+        SystemSet::on_enter(LevelState::GameOver)
+          .with_system(gameover::show_game_over),
+      )
+      .add_system_set(
+        SystemSet::on_update(LevelState::GameOver).with_system(gameover::return_to_menu),
+      )
+      .add_system_set(
+        SystemSet::on_exit(LevelState::GameOver)
+          .with_system(cleanup_system::<gameover::GameOverTag>),
+      )
       // disabled
       .add_system_set(
         SystemSet::on_enter(LevelState::Disabled)
           .with_system(cleanup_system::<LevelTag>)
           .with_system(cleanup_system::<camera::MainCamera>)
+          .with_system(ui::reset_stats)
+          .with_system(crate::systems::reset_rng)
           .with_system(complete::reset_level_count), //.with_system(despawn_player),
       );
   }
@@ -1,8 +1,9 @@
-use super::{LevelState, LevelTag};
+use super::{dungeon::Dungeon, LevelState, LevelTag};
 use crate::game::level::generator::Point;
 use crate::systems::{
-  AtlasAnimation, CombatAction, Combatant, Immortal, SimpleDirection, SpellType, Spellbook,
-  TopDownCharacter,
+  AtlasAnimation, CombatAction, CombatEvent, Combatant, Faction, GameTime, Immortal, Invulnerable,
+  Mana, PassiveDefinition, PassiveLibrary, SimpleDirection, SpellLibrary, SpellType, Spellbook,
+  Stunned, TopDownCharacter,
 };
 use bevy::{math::Vec3Swizzles, prelude::*};
 use heron::{
@@ -15,7 +16,6 @@ use super::camera::CameraTarget;
 use crate::systems::{MouseInfo, Movement, PhysicsLayers};
 
 mod animations;
-mod spells;
 
 #[derive(Component, Default)]
 pub struct PlayerComponent {
@@ -29,25 +29,130 @@ pub struct ActionQueue {
   pub pending_action_timer: Timer,
 }
 
+const PLAYER_SCALE: f32 = 0.1;
+const DASH_SCALE: f32 = 0.03; // the player shrinks while dashing
+
+// how far, how fast and how often the player can dash
+#[derive(Clone)]
+pub struct DashSettings {
+  pub max_distance: f32,
+  pub duration: f32,
+  pub invulnerability: f32, // immune for this long after the dash ends
+  pub cooldown: f32,        // seconds to recharge a single charge
+  pub max_charges: u32,
+}
+impl Default for DashSettings {
+  fn default() -> Self {
+    DashSettings {
+      max_distance: 100.,
+      duration: 0.1,
+      invulnerability: 0.1,
+      cooldown: 0.5,
+      max_charges: 2,
+    }
+  }
+}
+
 #[derive(Component, Default)]
 pub struct PlayerDash {
+  pub settings: DashSettings,
   pub origin: Vec2,
   pub direction: Vec2,
   pub distance: f32,
-  pub dash_duration: f32,
-  pub cooldown: f32,
-  pub on_cooldown: bool,
+  pub charges: u32,
+  pub active: bool, // still shrunk and passing through enemies
   pub timer: Timer,
   pub cd_timer: Timer,
   pub player_state_version: u32,
 }
 
+fn player_collision_layers() -> CollisionLayers {
+  CollisionLayers::none()
+    .with_group(PhysicsLayers::Player)
+    .with_mask(PhysicsLayers::Enemies)
+    .with_mask(PhysicsLayers::Attacks)
+    .with_mask(PhysicsLayers::World)
+    .with_mask(PhysicsLayers::Exit)
+}
+
+// enemies are passed through while dashing, walls are avoided by the dash target in read_input
+fn dashing_collision_layers() -> CollisionLayers {
+  player_collision_layers().without_mask(PhysicsLayers::Enemies)
+}
+
 #[derive(Component, Default)]
 pub struct PlayerSpells {
   pub timer: Timer,
   pub player_state_version: u32,
 }
 
+pub struct SpellBinding {
+  pub key: KeyCode,
+  pub label: &'static str,
+  pub spell_type: SpellType,
+}
+
+// keys that cast spells, in the order they are shown on the spell bar
+pub struct SpellBindings {
+  pub bindings: Vec<SpellBinding>,
+}
+impl Default for SpellBindings {
+  fn default() -> Self {
+    SpellBindings {
+      bindings: vec![
+        SpellBinding {
+          key: KeyCode::Key1,
+          label: "1",
+          spell_type: SpellType::BasicAttack,
+        },
+        SpellBinding {
+          key: KeyCode::Key2,
+          label: "2",
+          spell_type: SpellType::Nova,
+        },
+        SpellBinding {
+          key: KeyCode::Key3,
+          label: "3",
+          spell_type: SpellType::PiercingBolt,
+        },
+        SpellBinding {
+          key: KeyCode::Key4,
+          label: "4",
+          spell_type: SpellType::Blink,
+        },
+        SpellBinding {
+          key: KeyCode::Key5,
+          label: "5",
+          spell_type: SpellType::ChainLightning,
+        },
+        SpellBinding {
+          key: KeyCode::Key6,
+          label: "6",
+          spell_type: SpellType::BurningGround,
+        },
+        SpellBinding {
+          key: KeyCode::Key7,
+          label: "7",
+          spell_type: SpellType::SummonGoblins,
+        },
+      ],
+    }
+  }
+}
+
+impl SpellBindings {
+  // the player knows every bound spell that has been loaded, the rest stays as it is
+  fn learn(&self, library: &SpellLibrary, spellbook: &mut Spellbook) {
+    for binding in self.bindings.iter() {
+      if let Some(spell) = library.spells.get(&binding.spell_type) {
+        if !spellbook.spells.contains_key(&binding.spell_type) {
+          spellbook.spells.insert(binding.spell_type, spell.clone());
+        }
+      }
+    }
+  }
+}
+
 #[derive(Clone, Debug)]
 pub enum PlayerCommand {
   Stop,
@@ -103,6 +208,11 @@ fn spawn_player(
   mut commands: Commands,
   asset_server: Res<AssetServer>,
   mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+  spell_library: Res<SpellLibrary>,
+  bindings: Res<SpellBindings>,
+  dash_settings: Res<DashSettings>,
+  passive_library: Res<PassiveLibrary>,
+  passive_definitions: Res<Assets<PassiveDefinition>>,
 ) {
   let texture_handle = asset_server.load("player.png");
   let texture_atlas = TextureAtlas::from_grid(texture_handle, Vec2::new(256.0, 256.0), 24, 17);
@@ -110,11 +220,13 @@ fn spawn_player(
 
   let player = PlayerComponent::default();
   let initial_direction = Vec2::Y * -1.;
+  let mut spellbook = Spellbook::default();
+  bindings.learn(&spell_library, &mut spellbook);
 
   commands
     .spawn_bundle(SpriteSheetBundle {
       texture_atlas: texture_atlas_handle.clone(),
-      transform: Transform::from_scale(Vec3::splat(0.1)).with_translation(Vec3::new(
+      transform: Transform::from_scale(Vec3::splat(PLAYER_SCALE)).with_translation(Vec3::new(
         level.player_start_position.x as f32 * 16.0, // hax
         level.player_start_position.y as f32 * 16.0,
         crate::z::PLAYER,
@@ -136,15 +248,23 @@ fn spawn_player(
     // mark this as the player
     .insert(player)
     // we can get damaged and die
-    .insert(Combatant { hp: 1., hp_max: 1. })
-    // but we are immortal
-    .insert(Immortal)
+    .insert(Combatant {
+      hp: 100.,
+      hp_max: 100.,
+    })
+    // spells cost mana
+    .insert(Mana {
+      current: 100.,
+      max: 100.,
+      regen: 8.,
+    })
     // queue actions to feel more responsive
     .insert(ActionQueue::default())
     // we have a spellbook to cast spells
-    .insert(Spellbook {
-      spells: spells::build_spells(asset_server, texture_atlases),
-    })
+    .insert(spellbook)
+    .insert(Faction::Player)
+    // our hits can set off passives
+    .insert(passive_library.triggers(&passive_definitions))
     // we can cast spells
     .insert(PlayerSpells::default())
     // we can move around
@@ -156,8 +276,8 @@ fn spawn_player(
     })
     // we can dash
     .insert(PlayerDash {
-      dash_duration: 0.1,
-      cooldown: 0.5,
+      settings: dash_settings.clone(),
+      charges: dash_settings.max_charges,
       ..Default::default()
     })
     // physics
@@ -169,24 +289,19 @@ fn spawn_player(
     })
     .insert(RotationConstraints::lock())
     .insert(CollisionShape::Sphere { radius: 7. })
-    .insert(
-      CollisionLayers::none()
-        .with_group(PhysicsLayers::Player)
-        .with_mask(PhysicsLayers::Enemies)
-        .with_mask(PhysicsLayers::World)
-        .with_mask(PhysicsLayers::Exit),
-    );
+    .insert(player_collision_layers());
 }
 
 fn read_input(
   mouse_button_input: Res<Input<MouseButton>>,
   mouse_info: Res<MouseInfo>,
   keyboard_input: Res<Input<KeyCode>>,
+  spell_bindings: Res<SpellBindings>,
   physics_world: PhysicsWorld,
   mut evts: EventWriter<PlayerCommand>,
-  mut qry: Query<(&PlayerComponent, &Transform, &CollisionShape)>,
+  mut qry: Query<(&PlayerComponent, &Transform, &CollisionShape, &PlayerDash)>,
 ) {
-  if let Ok((_player, transform, shape)) = qry.get_single_mut() {
+  if let Ok((_player, transform, shape, dash)) = qry.get_single_mut() {
     let player_pos = transform.translation.xy();
     let result = physics_world.shape_cast_with_filter(
       &shape,
@@ -220,13 +335,20 @@ fn read_input(
         (mouse_info.world_pos2 - player_pos).normalize(),
       ));
     }
+    for binding in spell_bindings.bindings.iter() {
+      if keyboard_input.just_pressed(binding.key) {
+        evts.send(PlayerCommand::PrepareSpell(
+          binding.spell_type,
+          (mouse_info.world_pos2 - player_pos).normalize(),
+        ));
+      }
+    }
     if keyboard_input.just_pressed(KeyCode::Space) {
       if let Some(target_pos) = pos {
         let dist = (target_pos - player_pos).length();
-        if dist > 100. {
-          // TODO: parameterize max dash distance
+        if dist > dash.settings.max_distance {
           evts.send(PlayerCommand::Dash(
-            (target_pos - player_pos).normalize() * 100. + player_pos,
+            (target_pos - player_pos).normalize() * dash.settings.max_distance + player_pos,
           ));
         } else {
           evts.send(PlayerCommand::Dash(target_pos));
@@ -236,14 +358,75 @@ fn read_input(
   }
 }
 
+// F1 toggles god mode in debug builds
+fn toggle_god_mode(
+  mut commands: Commands,
+  keyboard_input: Res<Input<KeyCode>>,
+  qry: Query<(Entity, Option<&Immortal>), With<PlayerComponent>>,
+) {
+  if !cfg!(debug_assertions) || !keyboard_input.just_pressed(KeyCode::F1) {
+    return;
+  }
+  if let Ok((entity, immortal)) = qry.get_single() {
+    if immortal.is_some() {
+      commands.entity(entity).remove::<Immortal>();
+    } else {
+      commands.entity(entity).insert(Immortal);
+    }
+  }
+}
+
+fn stun_player(
+  qry: Query<&PlayerComponent, Added<Stunned>>,
+  mut evts: EventWriter<PlayerCommand>,
+) {
+  if !qry.is_empty() {
+    // interrupts whatever the player was doing, including spells
+    evts.send(PlayerCommand::Stop);
+  }
+}
+
+// dying wins over anything else that ends the level in the same frame, e.g. taking the stairs
+fn player_death(
+  qry: Query<Entity, With<PlayerComponent>>,
+  mut level_state: ResMut<State<LevelState>>,
+  mut dungeon: ResMut<Dungeon>,
+  mut evts: EventReader<CombatEvent>,
+) {
+  if let Ok(player) = qry.get_single() {
+    if evts
+      .iter()
+      .any(|evt| matches!(evt, CombatEvent::CombatantKilled(victim, _) if *victim == player))
+    {
+      dungeon.travelling = None;
+      if let Err(err) = level_state.overwrite_set(LevelState::GameOver) {
+        warn!("could not end the game: {:?}", err);
+      }
+    }
+  }
+}
+
+fn can_cast(spellbook: &Spellbook, mana: Option<&Mana>, spell_type: &SpellType) -> bool {
+  spellbook.spells.get(spell_type).map_or(false, |spell| {
+    spell.is_ready() && mana.map_or(true, |mana| mana.can_afford(spell.cost))
+  })
+}
+
+// the previous hit of a combo left the window open, cooldowns don't apply within a chain
+fn can_continue_combo(spellbook: &Spellbook, mana: Option<&Mana>, spell_type: &SpellType) -> bool {
+  spellbook.spells.get(spell_type).map_or(false, |spell| {
+    spell.combo_window_open() && mana.map_or(true, |mana| mana.can_afford(spell.cost))
+  })
+}
+
 fn process_cmd(
   evt: &PlayerCommand,
   character: &mut Mut<TopDownCharacter<PlayerAnimationState>>,
-  queue: &mut Mut<ActionQueue>,
   transform: &Transform,
   dash: &PlayerDash,
+  spellbook: &Spellbook,
+  mana: Option<&Mana>,
   player: &mut Mut<PlayerComponent>,
-  is_retry: bool,
 ) -> bool {
   match (evt, &player.state) {
     (PlayerCommand::Stop, _) => {
@@ -263,7 +446,7 @@ fn process_cmd(
       true
     }
     (PlayerCommand::Dash(dir), _) => {
-      if !dash.on_cooldown {
+      if dash.charges > 0 {
         player.state = PlayerStateMachine::Dashing(dir.clone());
         character.state = PlayerAnimationState::Dashing;
         character.direction_vec = (dir.clone() - transform.translation.xy()).normalize();
@@ -272,7 +455,30 @@ fn process_cmd(
       true
     }
     (PlayerCommand::PrepareSpell(spell_type, dir), PlayerStateMachine::Running(_))
-    | (PlayerCommand::PrepareSpell(spell_type, dir), PlayerStateMachine::Idle) => {
+    | (PlayerCommand::PrepareSpell(spell_type, dir), PlayerStateMachine::Idle)
+      if can_cast(spellbook, mana, spell_type) =>
+    {
+      player.state = PlayerStateMachine::PreparingSpell(*spell_type, dir.clone());
+      character.state = PlayerAnimationState::PreparingSpell;
+      character.direction_vec = dir.normalize();
+      player.version += 1;
+      true
+    }
+    // switch to another spell while still preparing, the old one is cancelled
+    (PlayerCommand::PrepareSpell(spell_type, dir), PlayerStateMachine::PreparingSpell(current, _))
+      if spell_type != current && can_cast(spellbook, mana, spell_type) =>
+    {
+      player.state = PlayerStateMachine::PreparingSpell(*spell_type, dir.clone());
+      character.state = PlayerAnimationState::PreparingSpell;
+      character.direction_vec = dir.normalize();
+      player.version += 1;
+      true
+    }
+    // casting the same spell again during recovery moves on to the next hit of its combo
+    (
+      PlayerCommand::PrepareSpell(spell_type, dir),
+      PlayerStateMachine::RecoveringFromSpell(current, _),
+    ) if spell_type == current && can_continue_combo(spellbook, mana, spell_type) => {
       player.state = PlayerStateMachine::PreparingSpell(*spell_type, dir.clone());
       character.state = PlayerAnimationState::PreparingSpell;
       character.direction_vec = dir.normalize();
@@ -297,13 +503,9 @@ fn process_cmd(
       player.version += 1;
       true
     }
-    (cmd, _) => {
+    (_cmd, _) => {
       // can't execute command
       //warn!("Unknown command");
-      if !is_retry {
-        queue.pending_action = Some(cmd.clone());
-        queue.pending_action_timer = Timer::from_seconds(0.2, false);
-      }
       false
     }
   }
@@ -311,25 +513,61 @@ fn process_cmd(
 
 fn update_state(
   mut evts: EventReader<PlayerCommand>,
+  mut combat_evts: EventWriter<CombatEvent>,
   mut qry: Query<(
+    Entity,
     &mut PlayerComponent,
     &mut ActionQueue,
     &mut TopDownCharacter<PlayerAnimationState>,
     &PlayerDash,
+    &Spellbook,
+    Option<&Mana>,
     &Transform,
+    Option<&Stunned>,
   )>,
-  time: Res<Time>,
+  time: Res<GameTime>,
 ) {
-  if let Ok((mut player, mut queue, mut character, dash, transform)) = qry.get_single_mut() {
+  if let Ok((
+    entity,
+    mut player,
+    mut queue,
+    mut character,
+    dash,
+    spellbook,
+    mana,
+    transform,
+    stunned,
+  )) = qry.get_single_mut()
+  {
+    let stunned = stunned.is_some();
     let mut event_processed = false;
     for evt in evts.iter() {
+      // stunned players can only stop
+      if event_processed || (stunned && !matches!(evt, PlayerCommand::Stop)) {
+        continue;
+      }
       event_processed =
-        event_processed || process_cmd(evt, &mut character, &mut queue, transform, dash, &mut player, false);
+        process_cmd(evt, &mut character, transform, dash, spellbook, mana, &mut player);
+      if !event_processed {
+        if let (PlayerCommand::PrepareSpell(spell_type, _), Some(mana)) = (evt, mana) {
+          let short_on_mana = spellbook
+            .spells
+            .get(spell_type)
+            .map_or(false, |spell| spell.is_ready() && !mana.can_afford(spell.cost));
+          if short_on_mana {
+            combat_evts.send(CombatEvent::NotEnoughMana(entity, transform.translation.xy()));
+          }
+        }
+        queue.pending_action = Some(evt.clone());
+        queue.pending_action_timer = Timer::from_seconds(0.2, false);
+      }
     }
 
     if let Some(cmd) = (&queue.pending_action).as_ref().map(|cmd| cmd.clone()) {
       // pending action, try and execute it
-      if process_cmd(&cmd, &mut character, &mut queue, transform, dash, &mut player, true) {
+      if !stunned
+        && process_cmd(&cmd, &mut character, transform, dash, spellbook, mana, &mut player)
+      {
         queue.pending_action = None;
         queue.pending_action_timer.reset();
       } else {
@@ -343,6 +581,20 @@ fn update_state(
   }
 }
 
+// spells that finished loading after the player spawned, or were added while the game runs
+fn learn_new_spells(
+  bindings: Res<SpellBindings>,
+  spell_library: Res<SpellLibrary>,
+  mut qry: Query<&mut Spellbook, With<PlayerComponent>>,
+) {
+  if !spell_library.is_changed() {
+    return;
+  }
+  for mut spellbook in qry.iter_mut() {
+    bindings.learn(&spell_library, &mut spellbook);
+  }
+}
+
 fn sync_spells(
   mut qry: Query<
     (Entity, &PlayerComponent, &mut PlayerSpells, &Spellbook),
@@ -357,6 +609,10 @@ fn sync_spells(
 
     match &player.state {
       PlayerStateMachine::PreparingSpell(spell_type, dir) => {
+        if !active_spell.timer.finished() {
+          // switched spells before the previous one was cast
+          evts.send(CombatAction::CancelSpell(entity));
+        }
         let spell = spells.spells.get(spell_type).expect("should find spell");
         active_spell.timer = Timer::from_seconds(spell.prepare_duration, false);
         evts.send(CombatAction::PrepareSpell(
@@ -394,7 +650,7 @@ fn sync_spells(
 }
 
 fn run_spells(
-  time: Res<Time>,
+  time: Res<GameTime>,
   mut qry: Query<(&PlayerComponent, &mut PlayerSpells)>,
   mut evts: EventWriter<PlayerCommand>,
 ) {
@@ -442,47 +698,78 @@ fn stop_when_destination_reached(
 }
 
 fn start_dash_player(
-  mut qry: Query<(&PlayerComponent, &mut PlayerDash, &mut Transform), Changed<PlayerComponent>>,
+  mut commands: Commands,
+  mut qry: Query<
+    (
+      Entity,
+      &PlayerComponent,
+      &mut PlayerDash,
+      &mut Transform,
+      &mut CollisionLayers,
+      Option<&mut Invulnerable>,
+    ),
+    Changed<PlayerComponent>,
+  >,
 ) {
-  for (player, mut dash, mut transform) in &mut qry.iter_mut() {
+  for (entity, player, mut dash, mut transform, mut layers, invulnerable) in &mut qry.iter_mut() {
     if let PlayerStateMachine::Dashing(target) = &player.state {
       if player.version > dash.player_state_version {
         dash.origin = transform.translation.xy();
         dash.direction = (target.clone() - dash.origin).normalize();
         dash.distance = (target.clone() - dash.origin).length();
-        dash.timer = Timer::from_seconds(dash.dash_duration, false);
-        dash.cd_timer = Timer::from_seconds(dash.cooldown, false);
-        dash.on_cooldown = true;
+        dash.timer = Timer::from_seconds(dash.settings.duration, false);
+        if dash.charges == dash.settings.max_charges {
+          dash.cd_timer = Timer::from_seconds(dash.settings.cooldown, false);
+        }
+        dash.charges -= 1;
+        dash.active = true;
         dash.player_state_version = player.version;
 
-        transform.scale.x = 0.03;
-        transform.scale.y = 0.03;
+        transform.scale.x = DASH_SCALE;
+        transform.scale.y = DASH_SCALE;
+        *layers = dashing_collision_layers();
+        // a finished timer is already being removed, so that one has to be replaced
+        let seconds = dash.settings.duration + dash.settings.invulnerability;
+        match invulnerable {
+          Some(mut invulnerable) if !invulnerable.timer.finished() => invulnerable.extend(seconds),
+          _ => {
+            commands.entity(entity).insert(Invulnerable::from_seconds(seconds));
+          }
+        }
       }
     }
   }
 }
 fn dash_player(
-  time: Res<Time>,
-  mut qry: Query<(&PlayerComponent, &mut Transform, &mut PlayerDash)>,
+  time: Res<GameTime>,
+  mut qry: Query<(&PlayerComponent, &mut Transform, &mut PlayerDash, &mut CollisionLayers)>,
   mut evts: EventWriter<PlayerCommand>,
 ) {
-  for (player, mut transform, mut dash) in &mut qry.iter_mut() {
+  for (player, mut transform, mut dash, mut layers) in &mut qry.iter_mut() {
+    // charges come back one at a time
+    if dash.charges < dash.settings.max_charges {
+      dash.cd_timer.tick(time.delta());
+      if dash.cd_timer.just_finished() {
+        dash.charges += 1;
+        dash.cd_timer = Timer::from_seconds(dash.settings.cooldown, false);
+      }
+    }
+
     if let PlayerStateMachine::Dashing(_) = &player.state {
-      let increments = (dash.direction * dash.distance) / dash.dash_duration;
+      let increments = (dash.direction * dash.distance) / dash.settings.duration;
 
       transform.translation.x += increments.x * time.delta_seconds();
       transform.translation.y += increments.y * time.delta_seconds();
       dash.timer.tick(time.delta());
       if dash.timer.just_finished() {
         evts.send(PlayerCommand::Stop);
-        transform.scale.x = 0.1;
-        transform.scale.y = 0.1;
-      }
-    } else {
-      dash.cd_timer.tick(time.delta());
-      if dash.cd_timer.just_finished() {
-        dash.on_cooldown = false;
       }
+    } else if dash.active {
+      // left the dashing state, either the dash finished or it was interrupted (e.g. by a stun)
+      dash.active = false;
+      transform.scale.x = PLAYER_SCALE;
+      transform.scale.y = PLAYER_SCALE;
+      *layers = player_collision_layers();
     }
   }
 }
@@ -493,6 +780,8 @@ impl Plugin for PlayerPlugin {
   fn build(&self, app: &mut App) {
     app
       .add_event::<PlayerCommand>()
+      .init_resource::<SpellBindings>()
+      .init_resource::<DashSettings>()
       .add_plugin(crate::systems::CharacterPlugin::<PlayerAnimationState>::default())
       .add_system_set(SystemSet::on_enter(LevelState::Loaded).with_system(spawn_player))
       .add_system_set(
@@ -505,10 +794,56 @@ impl Plugin for PlayerPlugin {
               .label("stop_move")
               .after("update_move"),
           )
-          .with_system(start_dash_player.label("start_dash").after("update_state"))
+          .with_system(
+            start_dash_player
+              .label("start_dash")
+              .after("update_state")
+              .after("tick_invulnerability"),
+          )
           .with_system(dash_player.label("update_dash").after("start_dash"))
+          .with_system(learn_new_spells)
           .with_system(sync_spells)
-          .with_system(run_spells),
+          .with_system(run_spells)
+          .with_system(toggle_god_mode)
+          .with_system(stun_player.before("input"))
+          .with_system(player_death),
       );
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::game::level::{dungeon::Stairs, systems::set_level_state};
+
+  #[test]
+  fn dying_on_the_stairs_ends_the_game() {
+    let mut world = World::default();
+    world.insert_resource(State::new(LevelState::Loaded));
+    world.insert_resource(Events::<CombatEvent>::default());
+    world.insert_resource(Dungeon::default());
+    let player = world.spawn().insert(PlayerComponent::default()).id();
+
+    // the stairs were taken earlier in the frame the player died
+    set_level_state(
+      &mut world.get_resource_mut::<State<LevelState>>().unwrap(),
+      LevelState::LevelComplete,
+    );
+    world.get_resource_mut::<Dungeon>().unwrap().travelling = Some(Stairs::Down);
+    world
+      .get_resource_mut::<Events<CombatEvent>>()
+      .unwrap()
+      .send(CombatEvent::CombatantKilled(player, player));
+
+    SystemStage::single_threaded()
+      .with_system(player_death)
+      .run(&mut world);
+    SystemStage::single_threaded()
+      .with_system_set(State::<LevelState>::get_driver())
+      .run(&mut world);
+
+    let state = world.get_resource::<State<LevelState>>().unwrap();
+    assert_eq!(*state.current(), LevelState::GameOver);
+    assert!(world.get_resource::<Dungeon>().unwrap().travelling.is_none());
+  }
+}
@@ -0,0 +1,119 @@
+use super::{
+  generator::{Level, Rect, TileType, WallType},
+  settings::LevelSettings,
+  systems::{spawn_collider, LevelCollider},
+  LevelTag,
+};
+use crate::systems::{CombatEvent, Combatant, Faction, PhysicsLayers};
+use bevy::{prelude::*, utils::HashSet};
+use bevy_ecs_tilemap::prelude::*;
+use heron::prelude::*;
+
+#[derive(Component)]
+pub struct BreakableWall {
+  pub secret_room: usize,
+}
+
+// rect is in tile coordinates
+pub fn spawn_breakable_wall(commands: &mut Commands, index: usize, rect: &Rect, tile_size: &Vec2) {
+  commands
+    .spawn()
+    .insert(Transform::from_translation(Vec3::new(
+      rect.x as f32 * tile_size.x + tile_size.x * (rect.width as f32 / 2.),
+      rect.y as f32 * tile_size.y + tile_size.y * (rect.height as f32 / 2.),
+      crate::z::WALLS,
+    )))
+    .insert(GlobalTransform::default())
+    .insert(LevelTag)
+    .insert(BreakableWall { secret_room: index })
+    .insert(Faction::Breakable)
+    // spells can knock it down
+    .insert(Combatant {
+      hp: 500.,
+      hp_max: 500.,
+    })
+    .insert(RigidBody::Static)
+    .insert(
+      CollisionLayers::none()
+        .with_group(PhysicsLayers::World)
+        .with_mask(PhysicsLayers::Player)
+        .with_mask(PhysicsLayers::Enemies)
+        .with_mask(PhysicsLayers::Attacks)
+        .with_mask(PhysicsLayers::MovementSensor)
+        .with_mask(PhysicsLayers::Corpses),
+    )
+    .insert(CollisionShape::Cuboid {
+      half_extends: Vec3::new(
+        rect.width as f32 * tile_size.x / 2.,
+        rect.height as f32 * tile_size.y / 2.,
+        0.0,
+      ),
+      border_radius: None,
+    });
+}
+
+pub fn break_walls(
+  mut commands: Commands,
+  mut map_query: MapQuery,
+  mut level: ResMut<Level>,
+  settings: Res<LevelSettings<WallType, TileType>>,
+  qry_walls: Query<&BreakableWall>,
+  qry_colliders: Query<(Entity, &LevelCollider)>,
+  mut evts: EventReader<CombatEvent>,
+) {
+  for evt in evts.iter() {
+    if let CombatEvent::CombatantKilled(victim, _) = evt {
+      let wall = match qry_walls.get(*victim) {
+        Ok(wall) => wall,
+        _ => continue,
+      };
+      commands.entity(*victim).despawn_recursive();
+
+      let region = match level.reveal_secret_room(wall.secret_room) {
+        Some(region) => region,
+        None => continue,
+      };
+
+      // redraw the floor and walls that changed
+      for x in region.x..region.x2 {
+        for y in region.y..region.y2 {
+          let position = TilePos(x as u32, y as u32);
+          let floor = settings.get_floor_tile(level.get(x, y));
+          let wall_tile = settings.get_wall_tile(level.get_wall(x, y));
+
+          for (layer_id, tile) in [(0u16, floor), (1u16, wall_tile)] {
+            let _ = map_query.despawn_tile(&mut commands, position, 0u16, layer_id);
+            if let Some(texture_index) = tile {
+              map_query
+                .set_tile(
+                  &mut commands,
+                  position,
+                  Tile {
+                    texture_index,
+                    ..Default::default()
+                  },
+                  0u16,
+                  layer_id,
+                )
+                .expect("should succeed");
+            }
+            map_query.notify_chunk_for_tile(position, 0u16, layer_id);
+          }
+        }
+      }
+
+      // swap out the colliders that were re-meshed
+      let shapes: HashSet<&Rect> = level.collission_shapes.iter().collect();
+      let existing: HashSet<&Rect> =
+        qry_colliders.iter().map(|(_, collider)| &collider.0).collect();
+      for (entity, collider) in qry_colliders.iter() {
+        if !shapes.contains(&collider.0) {
+          commands.entity(entity).despawn_recursive();
+        }
+      }
+      for rect in shapes.difference(&existing) {
+        spawn_collider(&mut commands, rect, &settings.tile_size);
+      }
+    }
+  }
+}
@@ -1,10 +1,13 @@
 use super::{
-  generator::{Level, TileType, WallType},
+  dungeon::{Dungeon, Stairs},
+  generator::{Level, Point, Rect, TileType, WallType},
+  player::PlayerComponent,
+  secrets::spawn_breakable_wall,
   settings::LevelSettings,
   LevelLoader, LevelState, LevelTag,
 };
-use crate::systems::PhysicsLayers;
-use bevy::prelude::*;
+use crate::systems::{Combatant, GameRng, PhysicsLayers};
+use bevy::{ecs::schedule::StateError, prelude::*};
 use bevy_ecs_tilemap::prelude::*;
 use heron::prelude::*;
 
@@ -20,16 +23,40 @@ use heron::prelude::*;
 //     .expect("set player state should always succeed");
 // }
 
+#[derive(Component)]
+pub struct LevelCollider(pub Rect);
+
+// the level can end in more than one way in the same frame (e.g. dying on the stairs), the first
+// transition that was queued wins. returns false if `next` was ignored
+pub fn set_level_state(level_state: &mut State<LevelState>, next: LevelState) -> bool {
+  match level_state.set(next.clone()) {
+    Ok(()) => true,
+    Err(StateError::StateAlreadyQueued) => {
+      debug!("level state is already changing, ignored {:?}", next);
+      false
+    }
+    Err(err) => {
+      warn!("could not change the level state to {:?}: {:?}", next, err);
+      false
+    }
+  }
+}
+
 pub fn generate_level(
   mut commands: Commands,
   mut map_query: MapQuery,
   mut level: ResMut<Level>,
+  mut dungeon: ResMut<Dungeon>,
+  mut rng: ResMut<GameRng>,
   settings: Res<LevelSettings<WallType, TileType>>,
 ) {
-  *level = Level::generate_random(
-    settings.map_size.0 * settings.chunk_size.0,
-    settings.map_size.1 * settings.chunk_size.1,
-  );
+  *level = dungeon.change_floor(std::mem::take(&mut *level), || {
+    Level::generate_random(
+      &mut rng.level,
+      settings.map_size.0 * settings.chunk_size.0,
+      settings.map_size.1 * settings.chunk_size.1,
+    )
+  });
 
   let map_entity = commands.spawn().id();
   let mut map = Map::new(0u16, map_entity);
@@ -89,49 +116,37 @@ pub fn generate_level(
 
   // spawn collission shapes
   for rect in level.collission_shapes.iter() {
-    commands
-      .spawn()
-      .insert(Transform::from_translation(Vec3::new(
-        (rect.x as f32 * layer_settings.tile_size.0
-          + layer_settings.tile_size.0 * (rect.width as f32 / 2.))
-          / 3.,
-        (rect.y as f32 * layer_settings.tile_size.1
-          + layer_settings.tile_size.1 * (rect.height as f32 / 2.))
-          / 3.,
-        crate::z::WALLS,
-      )))
-      .insert(LevelTag)
-      .insert(GlobalTransform::default())
-      .insert(RigidBody::Static)
-      .insert(
-        CollisionLayers::none()
-          .with_group(PhysicsLayers::World)
-          .with_mask(PhysicsLayers::Enemies)
-          .with_mask(PhysicsLayers::Attacks)
-          .with_mask(PhysicsLayers::MovementSensor)
-          .with_mask(PhysicsLayers::Corpses)
-
-      )
-      .insert(CollisionShape::Cuboid {
-        half_extends: Vec3::new(
-          rect.width as f32 * layer_settings.tile_size.0 / 6.,
-          rect.height as f32 * layer_settings.tile_size.1 / 6.,
-          0.0,
-        ),
-        border_radius: Some(0.1),
-      });
+    spawn_collider(&mut commands, rect, &settings.tile_size);
+  }
+
+  // spawn the walls hiding the secret rooms
+  for (index, secret) in level
+    .secret_rooms
+    .iter()
+    .enumerate()
+    .filter(|(_, secret)| !secret.revealed)
+  {
+    spawn_breakable_wall(&mut commands, index, &secret.wall, &settings.tile_size);
   }
 
   commands.spawn().insert(LevelTag).insert(LevelLoader {
     timer: Timer::from_seconds(1.0, false),
   });
 
+  spawn_stairs(&mut commands, Stairs::Down, &level.exit_point, &settings.tile_size);
+  if let Some(entry_point) = &level.entry_point {
+    spawn_stairs(&mut commands, Stairs::Up, entry_point, &settings.tile_size);
+  }
+}
+
+fn spawn_stairs(commands: &mut Commands, stairs: Stairs, point: &Point, tile_size: &Vec2) {
   commands
     .spawn()
     .insert(LevelTag)
+    .insert(stairs)
     .insert(Transform::from_translation(Vec3::new(
-      level.exit_point.x as f32 * layer_settings.tile_size.0 + layer_settings.tile_size.0 / 2.,
-      level.exit_point.y as f32 * layer_settings.tile_size.0 + layer_settings.tile_size.0 / 2.,
+      point.x as f32 * tile_size.x + tile_size.x / 2.,
+      point.y as f32 * tile_size.y + tile_size.y / 2.,
       0.,
     )))
     .insert(GlobalTransform::default())
@@ -142,11 +157,42 @@ pub fn generate_level(
         .with_mask(PhysicsLayers::Player),
     )
     .insert(CollisionShape::Cuboid {
-      half_extends: Vec3::new(settings.tile_size.x / 2., settings.tile_size.y / 2., 0.),
+      half_extends: Vec3::new(tile_size.x / 2., tile_size.y / 2., 0.),
       border_radius: None,
     });
 }
 
+// rect is in sub-tile coordinates (3x3 per tile)
+pub fn spawn_collider(commands: &mut Commands, rect: &Rect, tile_size: &Vec2) {
+  commands
+    .spawn()
+    .insert(Transform::from_translation(Vec3::new(
+      (rect.x as f32 * tile_size.x + tile_size.x * (rect.width as f32 / 2.)) / 3.,
+      (rect.y as f32 * tile_size.y + tile_size.y * (rect.height as f32 / 2.)) / 3.,
+      crate::z::WALLS,
+    )))
+    .insert(LevelTag)
+    .insert(LevelCollider(rect.clone()))
+    .insert(GlobalTransform::default())
+    .insert(RigidBody::Static)
+    .insert(
+      CollisionLayers::none()
+        .with_group(PhysicsLayers::World)
+        .with_mask(PhysicsLayers::Enemies)
+        .with_mask(PhysicsLayers::Attacks)
+        .with_mask(PhysicsLayers::MovementSensor)
+        .with_mask(PhysicsLayers::Corpses),
+    )
+    .insert(CollisionShape::Cuboid {
+      half_extends: Vec3::new(
+        rect.width as f32 * tile_size.x / 6.,
+        rect.height as f32 * tile_size.y / 6.,
+        0.0,
+      ),
+      border_radius: Some(0.1),
+    });
+}
+
 pub fn load_complete(
   time: Res<Time>,
   mut qry: Query<&mut LevelLoader>,
@@ -155,18 +201,23 @@ pub fn load_complete(
   for mut loader in (&mut qry).iter_mut() {
     loader.timer.tick(time.delta());
     if loader.timer.just_finished() {
-      level_state
-        .set(LevelState::Loaded)
-        .expect("set level state should always succeed");
+      set_level_state(&mut level_state, LevelState::Loaded);
     }
   }
 }
 
 pub fn check_level_complete(
   mut level_state: ResMut<State<LevelState>>,
+  mut dungeon: ResMut<Dungeon>,
   mut events: EventReader<CollisionEvent>,
+  qry: Query<&Stairs>,
+  qry_player: Query<&Combatant, With<PlayerComponent>>,
 ) {
-  events
+  // the dead don't take the stairs, `player_death` ends the game instead
+  if qry_player.get_single().map_or(true, |c| c.hp <= 0.) {
+    return;
+  }
+  if let Some(stairs) = events
     .iter()
     .filter_map(|event| {
       let (entity_1, entity_2) = event.rigid_body_entities();
@@ -183,9 +234,17 @@ pub fn check_level_complete(
         None
       }
     })
-    .for_each(|(_exit, _player, _e)| {
-      level_state
-        .set(LevelState::LevelComplete)
-        .expect("set level state should always succeed");
-    });
+    .filter(|(_exit, _player, e)| e.is_started())
+    .find_map(|(exit, _player, _e)| qry.get(exit).ok())
+  {
+    // floors that were visited before are just loaded again, without the level complete screen
+    let next_state = if dungeon.is_new_floor(*stairs) {
+      LevelState::LevelComplete
+    } else {
+      LevelState::Loading
+    };
+    if set_level_state(&mut level_state, next_state) {
+      dungeon.travelling = Some(*stairs);
+    }
+  }
 }
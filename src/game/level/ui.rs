@@ -1,3 +1,5 @@
+use super::player::{PlayerComponent, SpellBindings};
+use crate::systems::{Combatant, Mana, SpellLibrary, SpellType, Spellbook};
 use bevy::prelude::*;
 
 #[derive(Default)]
@@ -10,17 +12,29 @@ pub struct Stats {
 #[derive(Component)]
 pub struct StatsComponent;
 
+#[derive(Component)]
+pub struct ManaBar;
+
+#[derive(Component)]
+pub struct SpellBarCooldown(pub SpellType);
+
+#[derive(Component)]
+pub struct SpellBarCooldownText(pub SpellType);
+
 pub fn measure_time(
   time: Res<Time>,
   mut stats: ResMut<Stats>,
   mut qry: Query<&mut Text, With<StatsComponent>>,
+  qry_player: Query<&Combatant, With<PlayerComponent>>,
 ) {
   stats.time_elapsed += time.delta_seconds();
+  let hp = qry_player.get_single().map_or(0., |c| c.hp);
 
   for mut c in qry.iter_mut() {
     if stats.levels_completed > 0 {
       c.sections[0].value = format!(
-        " {} levels {} kills {:.0} kills/s",
+        "{:.0} hp {} levels {} kills {:.0} kills/s",
+        hp,
         stats.levels_completed,
         stats.kills,
         stats.kills as f32 / stats.time_elapsed,
@@ -28,7 +42,8 @@ pub fn measure_time(
       );
     } else {
       c.sections[0].value = format!(
-        "{} kills {:.0} kills/s",
+        "{:.0} hp {} kills {:.0} kills/s",
+        hp,
         stats.kills,
         stats.kills as f32 / stats.time_elapsed
       );
@@ -36,10 +51,26 @@ pub fn measure_time(
   }
 }
 
+pub fn update_mana_bar(
+  qry_player: Query<&Mana, With<PlayerComponent>>,
+  mut qry: Query<&mut Style, With<ManaBar>>,
+) {
+  let filled = qry_player
+    .get_single()
+    .map_or(0., |mana| (mana.current / mana.max.max(1.)).clamp(0., 1.));
+  for mut style in qry.iter_mut() {
+    style.size.width = Val::Percent(filled * 100.);
+  }
+}
+
 pub fn count_levels(mut stats: ResMut<Stats>) {
   stats.levels_completed += 1;
 }
 
+pub fn reset_stats(mut stats: ResMut<Stats>) {
+  *stats = Stats::default();
+}
+
 pub fn create_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
   let font = asset_server.load("Shizuru-Regular.ttf");
 
@@ -85,5 +116,182 @@ pub fn create_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
             })
             .insert(StatsComponent);
         });
+      // mana is spent on every cast, so it gets a bar under the stats
+      parent
+        .spawn_bundle(NodeBundle {
+          style: Style {
+            size: Size::new(Val::Px(400.0), Val::Px(24.0)),
+            margin: Rect::all(Val::Px(10.0)),
+            ..Default::default()
+          },
+          color: Color::rgb(0.1, 0.1, 0.2).into(),
+          ..Default::default()
+        })
+        .with_children(|parent| {
+          parent
+            .spawn_bundle(NodeBundle {
+              style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                ..Default::default()
+              },
+              color: Color::rgb(0.2, 0.4, 0.9).into(),
+              ..Default::default()
+            })
+            .insert(ManaBar);
+        });
+    });
+}
+
+// initials of the spell's name, so BasicAttack, Blink and BurningGround can be told apart
+fn abbreviation(spell_type: SpellType) -> String {
+  format!("{:?}", spell_type)
+    .chars()
+    .filter(|c| c.is_uppercase())
+    .collect()
+}
+
+pub fn create_spell_bar(
+  mut commands: Commands,
+  asset_server: Res<AssetServer>,
+  bindings: Res<SpellBindings>,
+  library: Res<SpellLibrary>,
+) {
+  let font = asset_server.load("Shizuru-Regular.ttf");
+  let text_style = |font_size: f32| TextStyle {
+    font: font.clone(),
+    font_size,
+    color: Color::rgb(0.9, 0.9, 0.9),
+  };
+
+  commands
+    .spawn_bundle(NodeBundle {
+      style: Style {
+        position_type: PositionType::Absolute,
+        position: Rect {
+          bottom: Val::Px(10.0),
+          left: Val::Px(0.0),
+          right: Val::Px(0.0),
+          top: Val::Auto,
+        },
+        justify_content: JustifyContent::Center,
+        ..Default::default()
+      },
+      color: Color::NONE.into(),
+      ..Default::default()
+    })
+    .insert(super::LevelTag)
+    .with_children(|parent| {
+      for binding in bindings.bindings.iter() {
+        let spell = library.spells.get(&binding.spell_type);
+        parent
+          .spawn_bundle(NodeBundle {
+            style: Style {
+              size: Size::new(Val::Px(64.0), Val::Px(64.0)),
+              margin: Rect::all(Val::Px(5.0)),
+              justify_content: JustifyContent::Center,
+              align_items: AlignItems::Center,
+              ..Default::default()
+            },
+            // spells are shown by their initials in the colour of their damage
+            color: spell
+              .map_or(Color::DARK_GRAY, |spell| spell.damage_type.color() * 0.5)
+              .into(),
+            ..Default::default()
+          })
+          .with_children(|parent| {
+            let name = abbreviation(binding.spell_type);
+            parent.spawn_bundle(TextBundle {
+              text: Text::with_section(name, text_style(32.0), Default::default()),
+              ..Default::default()
+            });
+            // covers the slot while the spell is on cooldown
+            parent
+              .spawn_bundle(NodeBundle {
+                style: Style {
+                  position_type: PositionType::Absolute,
+                  position: Rect {
+                    bottom: Val::Px(0.0),
+                    left: Val::Px(0.0),
+                    right: Val::Auto,
+                    top: Val::Auto,
+                  },
+                  size: Size::new(Val::Percent(100.0), Val::Percent(0.0)),
+                  ..Default::default()
+                },
+                color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+                ..Default::default()
+              })
+              .insert(SpellBarCooldown(binding.spell_type));
+            parent
+              .spawn_bundle(TextBundle {
+                style: Style {
+                  position_type: PositionType::Absolute,
+                  ..Default::default()
+                },
+                text: Text::with_section("", text_style(24.0), Default::default()),
+                ..Default::default()
+              })
+              .insert(SpellBarCooldownText(binding.spell_type));
+            parent.spawn_bundle(TextBundle {
+              style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                  top: Val::Px(2.0),
+                  left: Val::Px(4.0),
+                  right: Val::Auto,
+                  bottom: Val::Auto,
+                },
+                ..Default::default()
+              },
+              text: Text::with_section(binding.label, text_style(16.0), Default::default()),
+              ..Default::default()
+            });
+          });
+      }
     });
 }
+
+pub fn update_spell_bar(
+  qry_player: Query<&Spellbook, With<PlayerComponent>>,
+  mut qry_cooldowns: Query<(&SpellBarCooldown, &mut Style)>,
+  mut qry_texts: Query<(&SpellBarCooldownText, &mut Text)>,
+) {
+  let spellbook = match qry_player.get_single() {
+    Ok(spellbook) => spellbook,
+    _ => return,
+  };
+
+  for (cooldown, mut style) in qry_cooldowns.iter_mut() {
+    let remaining = spellbook
+      .spells
+      .get(&cooldown.0)
+      .map_or(0., |spell| spell.cooldown_remaining());
+    style.size.height = Val::Percent(remaining * 100.);
+  }
+  for (cooldown, mut text) in qry_texts.iter_mut() {
+    text.sections[0].value = match spellbook.spells.get(&cooldown.0) {
+      Some(spell) if !spell.is_ready() => {
+        format!("{:.1}", spell.cooldown_remaining() * spell.cooldown)
+      }
+      _ => String::new(),
+    };
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use bevy::utils::HashSet;
+
+  #[test]
+  fn spell_bar_abbreviations_are_unique() {
+    let bindings = SpellBindings::default();
+    let abbreviations: HashSet<String> = bindings
+      .bindings
+      .iter()
+      .map(|binding| abbreviation(binding.spell_type))
+      .collect();
+    assert_eq!(abbreviations.len(), bindings.bindings.len());
+    assert_eq!(abbreviation(SpellType::BurningGround), "BG");
+  }
+}
@@ -2,6 +2,7 @@ use crate::game::level::generator::TileType;
 use crate::game::level::generator::WallType;
 use crate::game::level::settings::LevelSettings;
 use bevy::{
+  asset::AssetServerSettings,
   diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin},
   prelude::*,
 };
@@ -34,6 +35,11 @@ fn main() {
       ..Default::default()
     })
     .insert_resource(ClearColor(Color::rgb(0.1568627450980392, 0.1568627450980392, 0.1568627450980392)))
+    // spell definitions are reloaded when they change on disk
+    .insert_resource(AssetServerSettings {
+      watch_for_changes: true,
+      ..Default::default()
+    })
     .add_state(GameState::Splash)
     .add_plugins(DefaultPlugins)
     .add_plugin(EguiPlugin)
@@ -41,7 +47,11 @@ fn main() {
     // .add_plugin(FrameTimeDiagnosticsPlugin::default())
     .add_plugin(systems::AudioPlugin)
     .add_plugin(systems::AnimationPlugin)
+    .add_plugin(systems::RngPlugin)
+    .add_plugin(systems::GameTimePlugin)
     .add_plugin(systems::CombatPlugin)
+    .add_plugin(systems::SpellsPlugin)
+    .add_plugin(systems::TriggersPlugin)
     //.add_plugin(systems::DebugPlugin)
     //.add_plugin(InspectorPlugin::<Data>::new())
     //.add_plugin(ShapePlugin)
@@ -1,3 +1,4 @@
+use crate::systems::{GameRng, GameTime};
 use bevy::{prelude::*, utils::Duration};
 use rand::distributions::{Distribution, Uniform};
 
@@ -53,7 +54,7 @@ impl TimedLife {
 
 fn despawn_timed_lives(
   mut commands: Commands,
-  time: Res<Time>,
+  time: Res<GameTime>,
   mut qry: Query<(Entity, &mut TimedLife)>,
 ) {
   for (entity, mut life) in qry.iter_mut() {
@@ -65,6 +66,7 @@ fn despawn_timed_lives(
 }
 
 fn init_atlas_animation(
+  mut rng: ResMut<GameRng>,
   mut qry: Query<
     (
       &mut AtlasAnimation,
@@ -78,15 +80,13 @@ fn init_atlas_animation(
   >,
 ) {
   if !qry.is_empty() {
-    let mut rng = rand::thread_rng();
-
     for (mut anim, def, mut sprite) in qry.iter_mut() {
       anim.timer = Timer::from_seconds(1. / def.fps, true);
       anim.enabled = true;
 
       anim.start_frame = if def.random_start {
         let between = Uniform::from(def.start..(def.end + 1));
-        between.sample(&mut rng)
+        between.sample(&mut rng.animation)
       } else {
         def.start
       };
@@ -97,7 +97,7 @@ fn init_atlas_animation(
 }
 
 fn animate_sprites(
-  time: Res<Time>,
+  time: Res<GameTime>,
   mut query: Query<(
     &mut AtlasAnimation,
     &AtlasAnimationDefinition,
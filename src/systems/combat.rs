@@ -1,12 +1,38 @@
-use crate::systems::{AtlasAnimation, AtlasAnimationDefinition, PhysicsLayers, TimedLife};
+use crate::systems::{
+  AtlasAnimation, AtlasAnimationDefinition, GameRng, GameTime, Movement, PhysicsLayers, TimedLife,
+};
 use bevy::{math::Vec3Swizzles, prelude::*, utils::HashMap};
-use heron::prelude::*;
-use rand::distributions::{Distribution, Uniform};
+use heron::{
+  prelude::*,
+  rapier_plugin::{PhysicsWorld, ShapeCastCollisionType},
+};
+use rand::{
+  distributions::{Distribution, Uniform},
+  Rng,
+};
+use serde::Deserialize;
+use std::{collections::VecDeque, time::Duration};
 
 #[derive(Debug)]
 pub enum CombatEvent {
-  DamageApplied(Entity, f32, Vec2, bool),
+  DamageApplied(Damage),
   CombatantKilled(Entity, Entity),
+  // caster, position
+  NotEnoughMana(Entity, Vec2),
+}
+
+#[derive(Debug)]
+pub struct Damage {
+  pub victim: Entity,
+  pub source: Entity,
+  pub spell: Option<SpellType>, // none for contact damage
+  pub amount: f32,
+  pub overkill: f32, // damage beyond what was needed to kill
+  pub position: Vec2,
+  pub fatal: bool,
+  pub damage_type: DamageType,
+  pub crit: bool,
+  pub depth: u32, // 0 for direct hits, increases every time a trigger causes damage
 }
 
 #[derive(Debug)]
@@ -23,17 +49,366 @@ pub struct Combatant {
   pub hp_max: f32,
 }
 
+// spent when casting spells, casters without it cast for free
+#[derive(Component)]
+pub struct Mana {
+  pub current: f32,
+  pub max: f32,
+  pub regen: f32, // per second
+}
+impl Mana {
+  pub fn can_afford(&self, cost: f32) -> bool {
+    self.current >= cost
+  }
+}
+
+#[derive(Hash, Copy, Clone, Eq, PartialEq, Debug, Deserialize)]
+pub enum DamageType {
+  Physical,
+  Fire,
+  Frost,
+  Shadow,
+  Poison,
+  Lightning,
+}
+impl DamageType {
+  pub fn color(&self) -> Color {
+    match self {
+      DamageType::Physical => Color::WHITE,
+      DamageType::Fire => Color::ORANGE_RED,
+      DamageType::Frost => Color::CYAN,
+      DamageType::Shadow => Color::VIOLET,
+      DamageType::Poison => Color::LIME_GREEN,
+      DamageType::Lightning => Color::YELLOW,
+    }
+  }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Hit {
+  pub amount: f32,
+  pub damage_type: DamageType,
+  pub crit: bool,
+  pub spell: Option<SpellType>,
+  pub depth: u32,
+}
+
+// 1 is immune, 0.5 halves the damage and negative values are vulnerabilities
+#[derive(Component, Clone, Default)]
+pub struct Resistances {
+  pub resistances: HashMap<DamageType, f32>,
+}
+impl Resistances {
+  pub fn multiplier(&self, damage_type: DamageType) -> f32 {
+    1. - self.resistances.get(&damage_type).copied().unwrap_or(0.)
+  }
+}
+
+// god mode, damage is ignored entirely
 #[derive(Component)]
 pub struct Immortal;
 
+// temporary immunity, e.g. right after being hit
+#[derive(Component)]
+pub struct Invulnerable {
+  pub timer: Timer,
+}
+impl Invulnerable {
+  pub fn from_seconds(seconds: f32) -> Self {
+    Invulnerable {
+      timer: Timer::from_seconds(seconds, false),
+    }
+  }
+
+  // keeps whichever immunity lasts longer
+  pub fn extend(&mut self, seconds: f32) {
+    let remaining = self.timer.duration().as_secs_f32() - self.timer.elapsed_secs();
+    if remaining < seconds {
+      self.timer = Timer::from_seconds(seconds, false);
+    }
+  }
+}
+
+// damage dealt by touching the entity
+#[derive(Component)]
+pub struct ContactDamage {
+  pub damage: f32,
+  pub invulnerability: f32, // how long the victim is immune after being hit
+}
+
+// summoned units, damage and kills they deal are credited to the owner
+#[derive(Component, Copy, Clone)]
+pub struct Owner {
+  pub entity: Entity,
+}
+
+#[derive(Component, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Faction {
+  Player,
+  Monsters,
+  Neutral, // not part of any fight, anyone can damage it
+  Ally,
+  Breakable, // only the player's side can damage it, e.g. walls hiding secret rooms
+}
+impl Faction {
+  pub fn is_hostile_to(&self, other: &Faction) -> bool {
+    match (self, other) {
+      (Faction::Neutral | Faction::Breakable, _) | (_, Faction::Neutral | Faction::Breakable) => {
+        false
+      }
+      (Faction::Monsters, Faction::Monsters) => false,
+      (Faction::Monsters, _) | (_, Faction::Monsters) => true,
+      _ => false,
+    }
+  }
+
+  // attacks also hit neutral combatants so they can be destroyed
+  pub fn can_damage(&self, other: &Faction) -> bool {
+    match other {
+      Faction::Neutral => true,
+      Faction::Breakable => matches!(self, Faction::Player | Faction::Ally),
+      _ => self.is_hostile_to(other),
+    }
+  }
+}
+
+#[derive(Hash, Copy, Clone, Eq, PartialEq, Debug, Deserialize)]
+pub enum StatusEffectType {
+  Burn,
+  Slow,
+  Stun,
+  Poison,
+}
+impl StatusEffectType {
+  // only burn and poison deal damage on each tick
+  pub fn damage_type(&self) -> Option<DamageType> {
+    match self {
+      StatusEffectType::Burn => Some(DamageType::Fire),
+      StatusEffectType::Poison => Some(DamageType::Poison),
+      _ => None,
+    }
+  }
+
+  pub fn tint(&self) -> Color {
+    match self {
+      StatusEffectType::Burn => Color::ORANGE,
+      StatusEffectType::Slow => Color::rgb(0.6, 0.8, 1.0),
+      StatusEffectType::Stun => Color::YELLOW,
+      StatusEffectType::Poison => Color::LIME_GREEN,
+    }
+  }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct StatusEffectDefinition {
+  pub effect_type: StatusEffectType,
+  pub duration: f32,
+  pub tick_interval: f32,
+  pub damage_per_tick: f32, // per stack
+  pub slow: f32,            // fraction of the movement speed that is taken away
+  pub max_stacks: u32,      // reapplying adds a stack up to this, or just refreshes the duration
+}
+
+// times are game time, so effects can be checked every frame without touching them
+pub struct StatusEffect {
+  pub definition: StatusEffectDefinition,
+  pub source: Entity,
+  pub spell: Option<SpellType>, // spell that applied the effect
+  pub stacks: u32,
+  pub expires: f64,   // when the effect wears off
+  pub next_tick: f64, // when the effect ticks next
+}
+impl StatusEffect {
+  // effects that don't deal damage never tick, they only wear off
+  fn tick_due(&self, now: f64) -> bool {
+    self.definition.effect_type.damage_type().is_some() && self.next_tick <= now.min(self.expires)
+  }
+
+  pub fn is_due(&self, now: f64) -> bool {
+    self.expires <= now || self.tick_due(now)
+  }
+
+  // true if the effect ticks now, the tick after it is scheduled straight away
+  pub fn tick(&mut self, now: f64) -> bool {
+    if !self.tick_due(now) {
+      return false;
+    }
+    self.next_tick += self.definition.tick_interval.max(0.001) as f64;
+    true
+  }
+}
+
+#[derive(Component, Default)]
+pub struct StatusEffects {
+  pub effects: Vec<StatusEffect>,
+  pub base_speed: Option<f32>,   // movement speed before any slows
+  pub base_color: Option<Color>, // sprite colour before any tint
+}
+impl StatusEffects {
+  pub fn apply(
+    &mut self,
+    definition: &StatusEffectDefinition,
+    source: Entity,
+    spell: Option<SpellType>,
+    now: f64,
+  ) {
+    let expires = now + definition.duration as f64;
+    if let Some(effect) = self
+      .effects
+      .iter_mut()
+      .find(|e| e.definition.effect_type == definition.effect_type && e.source == source)
+    {
+      effect.stacks = (effect.stacks + 1).min(definition.max_stacks.max(1));
+      effect.expires = expires;
+    } else {
+      self.effects.push(StatusEffect {
+        definition: definition.clone(),
+        source,
+        spell,
+        stacks: 1,
+        expires,
+        next_tick: now + definition.tick_interval.max(0.001) as f64,
+      });
+    }
+  }
+
+  pub fn has(&self, effect_type: StatusEffectType) -> bool {
+    self
+      .effects
+      .iter()
+      .any(|e| e.definition.effect_type == effect_type)
+  }
+
+  pub fn slow(&self) -> f32 {
+    self
+      .effects
+      .iter()
+      .filter(|e| e.definition.effect_type == StatusEffectType::Slow)
+      .map(|e| e.definition.slow)
+      .fold(0., f32::max)
+  }
+}
+
+// can't move or cast
+#[derive(Component)]
+pub struct Stunned;
+
+// how an entity reacts to being hit, entities without it are never knocked back
+#[derive(Component, Clone)]
+pub struct HitReaction {
+  pub mass: f32,    // heavier entities are pushed less
+  pub stagger: f32, // seconds of suspended steering after a hit
+}
+
+// steering is suspended so knockback isn't cancelled out straight away
+#[derive(Component)]
+pub struct Staggered {
+  pub timer: Timer,
+}
+
+// how an area picks its victims, only `Sensor` uses physics
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+pub enum AreaShape {
+  Sensor,
+  Cone { angle: f32, range: f32 }, // angle in degrees, centred on the cast direction
+  Ring { inner: f32, outer: f32 },
+  Chain { jumps: u32, range: f32, falloff: f32 }, // each jump multiplies the damage by falloff
+}
+impl Default for AreaShape {
+  fn default() -> Self {
+    AreaShape::Sensor
+  }
+}
+impl AreaShape {
+  // whether a target at `position` is inside a cone or ring cast from `origin`, the other shapes
+  // don't have a fixed area
+  pub fn contains(&self, origin: Vec2, direction: Vec2, position: Vec2) -> bool {
+    let diff = position - origin;
+    match *self {
+      AreaShape::Cone { angle, range } => {
+        diff.length() <= range && direction.angle_between(diff).abs() <= angle.to_radians() / 2.
+      }
+      AreaShape::Ring { inner, outer } => diff.length() >= inner && diff.length() <= outer,
+      AreaShape::Sensor | AreaShape::Chain { .. } => false,
+    }
+  }
+
+  // damage multiplier for the nth victim
+  pub fn falloff(&self, index: usize) -> f32 {
+    match self {
+      AreaShape::Chain { falloff, .. } => falloff.powi(index as i32),
+      _ => 1.,
+    }
+  }
+}
+
 #[derive(Component)]
 pub struct AreaOfEffect {
   pub caster: Entity,
+  pub spell: SpellType,
+  pub area: AreaShape,
+  pub faction: Faction, // faction of the caster when the spell was cast
   pub damage_min: f32,
   pub damage_max: f32,
+  pub damage_type: DamageType,
+  pub crit_chance: f32,
+  pub crit_multiplier: f32,
+  pub knockback: f32,
+  pub status_effects: Vec<StatusEffectDefinition>,
   pub tick_timer: Timer,
+  pub dot_interval: Option<f32>, // damage over time ticks each victim on its own schedule
+  pub age: f32,
+  pub lifetime: f32, // nothing is hit after this, even if the area hasn't been despawned yet
+  pub last_ticks: HashMap<Entity, f32>, // age of the last tick per victim, kept after they leave
   pub victims: Vec<Entity>,
 }
+impl AreaOfEffect {
+  // advances the area's clock and returns the victims due for a hit, with their index for falloff
+  pub fn tick(&mut self, delta: Duration) -> Vec<(usize, Entity)> {
+    self.tick_timer.tick(delta);
+    self.age += delta.as_secs_f32();
+    if self.age > self.lifetime {
+      return Vec::new();
+    }
+
+    // victims get their first tick on entering, leaving and coming back doesn't reset it
+    match self.dot_interval {
+      Some(interval) => self
+        .victims
+        .iter()
+        .copied()
+        .enumerate()
+        .filter(|(_, victim)| {
+          self.last_ticks.get(victim).map_or(true, |last| self.age - last >= interval)
+        })
+        .collect(),
+      None if self.tick_timer.just_finished() => {
+        self.victims.iter().copied().enumerate().collect()
+      }
+      None => Vec::new(),
+    }
+  }
+
+  pub fn record_tick(&mut self, victim: Entity) {
+    self.last_ticks.insert(victim, self.age);
+  }
+
+  // stops hitting right away, the entity itself only goes away when commands are applied
+  pub fn expire(&mut self) {
+    self.lifetime = -1.;
+    self.victims.clear();
+  }
+}
+
+#[derive(Component)]
+pub struct Projectile {
+  pub origin: Vec2,
+  pub direction: Vec2,
+  pub velocity: f32,
+  pub max_range: f32,
+  pub hits_remaining: u32,
+  pub hit: Vec<Entity>,
+}
 
 #[derive(Default)]
 pub struct FlyingTextSettings {
@@ -41,32 +416,119 @@ pub struct FlyingTextSettings {
   pub alignment: TextAlignment,
 }
 
+// damage numbers are recycled instead of spawning a new text for every hit
+pub struct FlyingTextPool {
+  pub max_size: usize,
+  pub free: Vec<Entity>,
+  pub active: VecDeque<Entity>,
+}
+impl Default for FlyingTextPool {
+  fn default() -> Self {
+    FlyingTextPool {
+      max_size: 200,
+      free: Vec::new(),
+      active: VecDeque::new(),
+    }
+  }
+}
+
+#[derive(Component)]
+pub struct FlyingText {
+  pub timer: Timer,
+  pub origin: Vec2,
+  pub rise: f32,
+  pub scale: f32,
+  pub color: Color,
+}
+
 #[derive(Component, Default)]
 pub struct Spellbook {
   pub spells: HashMap<SpellType, Spell>,
+  pub casts: u32,          // incremented every time a spell is prepared
+  pub active: Option<u32>, // the cast in progress, if any
+}
+
+// links everything spawned by a spell to the caster and the cast that spawned it
+#[derive(Component)]
+pub struct SpellInstance {
+  pub caster: Entity,
+  pub spell_type: SpellType,
+  pub cast: u32,
 }
 
-#[derive(Hash, Copy, Clone, Eq, PartialEq, Debug)]
+#[derive(Hash, Copy, Clone, Eq, PartialEq, Debug, Deserialize)]
 pub enum SpellType {
   BasicAttack,
+  Nova,
+  PiercingBolt,
+  Blink,
+  ChainLightning,
+  BurningGround,
+  SummonGoblins,
+  MinionSwipe,
+  GoblinSwipe,
+  EyeBolt,
 }
 
+#[derive(Clone)]
 pub struct Spell {
   pub status: SpellStatus,
   pub damage_min: f32,
   pub damage_max: f32,
+  pub damage_type: DamageType,
+  pub crit_chance: f32, // 0 to 1
+  pub crit_multiplier: f32,
+  pub knockback: f32, // pushes victims away from the caster
+  pub cost: f32,      // mana spent when the spell is cast
+  pub status_effects: Vec<StatusEffectDefinition>, // applied to every victim that gets hit
   pub dot: bool,
   pub damage_tick: f32,
+  pub ground_duration: f32, // how long the area stays after the cast, 0 if it doesn't
   pub prepare_duration: f32,
   pub cast_duration: f32,
   pub recovery_duration: f32,
+  pub cooldown: f32, // starts when the spell is cast
   pub prepare_sprite: Option<SpellSprite>,
   pub cast_sprite: Option<SpellSprite>,
   pub recovery_sprite: Option<SpellSprite>,
   pub projectile_sprite: Option<SpellSprite>,
   pub shape: CollisionShape,
-  pub projectile_velocity: f32,
+  pub area: AreaShape,
+  pub projectile_velocity: f32, // 0 if the spell is not a projectile
+  pub pierce: u32,              // number of victims a projectile passes through
+  pub max_range: f32,
+  pub blink: f32, // distance the caster teleports towards the target when cast
+  pub combo: Vec<ComboStage>,     // hits that follow the first one, empty without a combo
+  pub combo_window: f32,          // seconds into recovery the next hit can still be started
+  pub combo_stage: usize,         // 0 is the spell itself, 1 the first entry of `combo`
+  pub combo_timer: Option<Timer>, // running while the next hit can be started
+  pub summon: Option<Summon>,
+}
+
+// units summoned when the spell is cast, they fight for the caster until their time runs out
+#[derive(Clone)]
+pub struct Summon {
+  pub count: u32,
+  pub lifetime: f32,
+  pub max_hp: f32,
+  pub speed: f32,
+  pub texture_atlas: Handle<TextureAtlas>,
+  pub idle: AtlasAnimationDefinition,
+  pub attack: SpellType,
+  pub range: f32, // start casting once a target is this close
+}
+
+// a follow-up hit of a combo, anything not listed here is taken from the spell
+#[derive(Clone)]
+pub struct ComboStage {
+  pub damage_min: f32,
+  pub damage_max: f32,
+  pub cast_sprite: Option<SpellSprite>,
+  pub recovery_sprite: Option<SpellSprite>,
+  pub shape: CollisionShape,
 }
+
+#[derive(Clone)]
 pub struct SpellSprite {
   pub texture_atlas: Handle<TextureAtlas>,
   pub start_frame: usize,
@@ -76,24 +538,83 @@ pub struct SpellSprite {
   pub translation: Vec2, // used to offset the sprite
 }
 
+impl Spell {
+  pub fn is_ready(&self) -> bool {
+    matches!(self.status, SpellStatus::Ready)
+  }
+
+  // 1 right after casting, 0 once the spell is ready again
+  pub fn cooldown_remaining(&self) -> f32 {
+    match &self.status {
+      SpellStatus::Ready => 0.,
+      SpellStatus::Cooldown(timer) => 1. - timer.percent(),
+    }
+  }
+
+  fn start_cooldown(&mut self) {
+    if self.cooldown > 0. {
+      self.status = SpellStatus::Cooldown(Timer::from_seconds(self.cooldown, false));
+    }
+  }
+
+  pub fn combo_window_open(&self) -> bool {
+    self.combo_timer.is_some()
+  }
+
+  // the spell as it is cast at the given stage of its combo
+  pub fn at_combo_stage(&self, stage: usize) -> Spell {
+    let mut spell = self.clone();
+    if let Some(combo) = stage.checked_sub(1).and_then(|index| self.combo.get(index)) {
+      spell.damage_min = combo.damage_min;
+      spell.damage_max = combo.damage_max;
+      spell.cast_sprite = combo.cast_sprite.clone();
+      spell.recovery_sprite = combo.recovery_sprite.clone();
+      spell.shape = combo.shape.clone();
+    }
+    spell
+  }
+
+  // preparing inside the window continues the chain, otherwise it starts over
+  fn advance_combo(&mut self) {
+    self.combo_stage = match self.combo_timer.take() {
+      Some(_) => (self.combo_stage + 1) % (self.combo.len() + 1),
+      None => 0,
+    };
+  }
+
+  // after the final hit there is nothing left to continue
+  fn open_combo_window(&mut self) {
+    if self.combo_stage < self.combo.len() && self.combo_window > 0. {
+      self.combo_timer = Some(Timer::from_seconds(self.combo_window, false));
+    }
+  }
+}
+
+#[derive(Clone)]
 pub enum SpellStatus {
   Ready,
-  // Cooldown(Timer),
+  Cooldown(Timer),
 }
 
-fn find_victims(mut qry: Query<&mut AreaOfEffect>, mut events: EventReader<CollisionEvent>) {
+fn find_victims(
+  mut qry: Query<&mut AreaOfEffect>,
+  qry_faction: Query<&Faction>,
+  mut events: EventReader<CollisionEvent>,
+) {
   events
     .iter()
     .filter_map(|event| {
       let (entity_1, entity_2) = event.rigid_body_entities();
       let (layers_1, layers_2) = event.collision_layers();
-      if layers_1.contains_group(PhysicsLayers::Attacks)
-        && layers_2.contains_group(PhysicsLayers::Enemies)
-      {
+      // walls can be combatants too (breakable walls)
+      let is_target = |layers: CollisionLayers| {
+        layers.contains_group(PhysicsLayers::Enemies)
+          || layers.contains_group(PhysicsLayers::Player)
+          || layers.contains_group(PhysicsLayers::World)
+      };
+      if layers_1.contains_group(PhysicsLayers::Attacks) && is_target(layers_2) {
         Some((entity_2, entity_1, event))
-      } else if layers_2.contains_group(PhysicsLayers::Attacks)
-        && layers_1.contains_group(PhysicsLayers::Enemies)
-      {
+      } else if layers_2.contains_group(PhysicsLayers::Attacks) && is_target(layers_1) {
         Some((entity_1, entity_2, event))
       } else {
         None
@@ -101,8 +622,15 @@ fn find_victims(mut qry: Query<&mut AreaOfEffect>, mut events: EventReader<Colli
     })
     .for_each(|(enemy, attack, e)| {
       if let Ok(mut aoe) = qry.get_mut(attack) {
+        let faction = qry_faction.get(enemy).copied().unwrap_or(Faction::Neutral);
+        if !aoe.faction.can_damage(&faction) {
+          return;
+        }
         if e.is_started() {
-          aoe.victims.push(enemy);
+          // several colliders can start touching at once, only count the victim once
+          if !aoe.victims.contains(&enemy) {
+            aoe.victims.push(enemy);
+          }
         } else {
           aoe.victims.retain(|victim| *victim != enemy);
         }
@@ -110,38 +638,171 @@ fn find_victims(mut qry: Query<&mut AreaOfEffect>, mut events: EventReader<Colli
     });
 }
 
+// picked again every frame, so targets walking into a lingering area get hit as well
+fn find_area_victims(
+  mut qry: Query<(&mut AreaOfEffect, &Transform)>,
+  qry_targets: Query<(Entity, &Transform, Option<&Faction>), With<Combatant>>,
+) {
+  for (mut aoe, transform) in qry.iter_mut() {
+    let origin = transform.translation.xy();
+    let direction = (transform.rotation * Vec3::X).xy();
+    let (caster, faction, area) = (aoe.caster, aoe.faction, aoe.area);
+    let targets = qry_targets
+      .iter()
+      .filter(|(entity, _, target_faction)| {
+        *entity != caster
+          && faction.can_damage(&target_faction.copied().unwrap_or(Faction::Neutral))
+      })
+      .map(|(entity, target_transform, _)| (entity, target_transform.translation.xy()));
+
+    let victims: Vec<Entity> = match area {
+      AreaShape::Sensor => continue,
+      AreaShape::Cone { .. } | AreaShape::Ring { .. } => targets
+        .filter(|(_, position)| area.contains(origin, direction, *position))
+        .map(|(entity, _)| entity)
+        .collect(),
+      AreaShape::Chain { jumps, range, .. } => {
+        // start with the closest target in front of the caster, then keep jumping to the
+        // closest target that wasn't hit yet
+        let mut remaining: Vec<(Entity, Vec2)> = targets.collect();
+        let mut victims = Vec::new();
+        let mut from = origin;
+        while victims.len() <= jumps as usize {
+          let next = remaining
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, position))| {
+              position.distance(from) <= range
+                && (!victims.is_empty() || direction.dot(*position - origin) >= 0.)
+            })
+            .min_by(|(_, (_, a)), (_, (_, b))| {
+              a.distance(from)
+                .partial_cmp(&b.distance(from))
+                .unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .map(|(index, _)| index);
+          match next {
+            Some(index) => {
+              let (entity, position) = remaining.swap_remove(index);
+              victims.push(entity);
+              from = position;
+            }
+            None => break,
+          }
+        }
+        victims
+      }
+    };
+    aoe.victims = victims;
+  }
+}
+
 fn damage_victims(
-  time: Res<Time>,
-  mut qry: Query<&mut AreaOfEffect>,
-  mut combatant_query: Query<(Entity, &mut Combatant, &Transform)>,
+  mut commands: Commands,
+  time: Res<GameTime>,
+  mut qry: Query<(&mut AreaOfEffect, Option<&mut Projectile>)>,
+  mut combatant_query: Query<
+    (
+      Entity,
+      &mut Combatant,
+      &Transform,
+      Option<&Resistances>,
+      Option<&mut StatusEffects>,
+      Option<&HitReaction>,
+      Option<&mut Velocity>,
+    ),
+    (Without<Immortal>, Without<Invulnerable>),
+  >,
+  qry_caster: Query<&Transform>,
+  qry_owner: Query<&Owner>,
+  mut rng: ResMut<GameRng>,
   mut events: EventWriter<CombatEvent>,
 ) {
-  let mut rng = rand::thread_rng();
-  for mut aoe in qry.iter_mut() {
-    aoe.tick_timer.tick(time.delta());
+  let rng = &mut rng.combat;
+  let now = time.seconds_since_startup();
+  for (mut aoe, mut projectile) in qry.iter_mut() {
+    let aoe = &mut *aoe;
+    let source = qry_owner.get(aoe.caster).map_or(aoe.caster, |owner| owner.entity);
+    let due = aoe.tick(time.delta());
+    if due.is_empty() {
+      continue;
+    }
     let between = Uniform::from(aoe.damage_min..(aoe.damage_max + 1.));
 
-    if aoe.tick_timer.just_finished() {
-      for victim in &aoe.victims {
-        if let Ok((entity, mut c, transform)) = combatant_query.get_mut(*victim) {
-          if c.hp <= 0. {
+    for (index, victim) in due {
+      if let Ok((
+        entity,
+        mut c,
+        transform,
+        resistances,
+        status_effects,
+        hit_reaction,
+        velocity,
+      )) = combatant_query.get_mut(victim)
+      {
+        if c.hp <= 0. {
+          continue;
+        }
+
+        // projectiles only hit each victim once and stop after running out of pierce
+        if let Some(projectile) = projectile.as_mut() {
+          if projectile.hits_remaining == 0 || projectile.hit.contains(&victim) {
             continue;
           }
+          projectile.hits_remaining -= 1;
+          projectile.hit.push(victim);
+        }
+        aoe.record_tick(victim);
 
-          let damage = between.sample(&mut rng);
-
-          c.hp -= damage;
+        let crit = rng.gen_bool(aoe.crit_chance.clamp(0., 1.) as f64);
+        let multiplier = resistances.map_or(1., |r| r.multiplier(aoe.damage_type))
+          * if crit { aoe.crit_multiplier } else { 1. }
+          * aoe.area.falloff(index);
+        let damage = (between.sample(rng) * multiplier).max(0.);
+        apply_damage(
+          entity,
+          &mut c,
+          transform,
+          Hit {
+            amount: damage,
+            damage_type: aoe.damage_type,
+            crit,
+            spell: Some(aoe.spell),
+            depth: 0,
+          },
+          source,
+          &mut events,
+        );
 
-          events.send(CombatEvent::DamageApplied(
-            *victim,
-            damage,
-            transform.translation.xy(),
-            c.hp == 0.,
-          ));
+        if let (Some(hit_reaction), Some(mut velocity), Ok(caster_transform)) =
+          (hit_reaction, velocity, qry_caster.get(aoe.caster))
+        {
+          if damage > 0. && c.hp > 0. && aoe.knockback > 0. {
+            let direction = (transform.translation.xy() - caster_transform.translation.xy())
+              .normalize_or_zero();
+            velocity.linear +=
+              Vec3::from((direction * aoe.knockback / hit_reaction.mass.max(0.1), 0.));
+            commands.entity(entity).insert(Staggered {
+              timer: Timer::from_seconds(hit_reaction.stagger, false),
+            });
+          }
+        }
 
-          if c.hp <= 0. {
-            c.hp = 0.;
-            events.send(CombatEvent::CombatantKilled(entity, aoe.caster));
+        if damage <= 0. || c.hp <= 0. || aoe.status_effects.is_empty() {
+          continue;
+        }
+        match status_effects {
+          Some(mut status_effects) => {
+            for definition in aoe.status_effects.iter() {
+              status_effects.apply(definition, source, Some(aoe.spell), now);
+            }
+          }
+          None => {
+            let mut status_effects = StatusEffects::default();
+            for definition in aoe.status_effects.iter() {
+              status_effects.apply(definition, source, Some(aoe.spell), now);
+            }
+            commands.entity(entity).insert(status_effects);
           }
         }
       }
@@ -149,48 +810,472 @@ fn damage_victims(
   }
 }
 
+fn tick_status_effects(
+  time: Res<GameTime>,
+  mut qry: Query<(
+    Entity,
+    &mut StatusEffects,
+    &mut Combatant,
+    &Transform,
+    Option<&Resistances>,
+  )>,
+  mut events: EventWriter<CombatEvent>,
+) {
+  let now = time.seconds_since_startup();
+  for (entity, mut status_effects, mut c, transform, resistances) in qry.iter_mut() {
+    // only borrowed mutably when something happens, `update_status_effects` relies on that
+    if !status_effects.effects.iter().any(|effect| effect.is_due(now)) {
+      continue;
+    }
+    for effect in status_effects.effects.iter_mut() {
+      if !effect.tick(now) || c.hp <= 0. {
+        continue;
+      }
+      if let Some(damage_type) = effect.definition.effect_type.damage_type() {
+        let multiplier = resistances.map_or(1., |r| r.multiplier(damage_type));
+        let damage = effect.definition.damage_per_tick * effect.stacks as f32 * multiplier;
+        apply_damage(
+          entity,
+          &mut c,
+          transform,
+          Hit {
+            amount: damage.max(0.),
+            damage_type,
+            crit: false,
+            spell: effect.spell,
+            depth: 0,
+          },
+          effect.source,
+          &mut events,
+        );
+      }
+    }
+    status_effects.effects.retain(|effect| effect.expires > now);
+  }
+}
+
+fn update_status_effects(
+  mut commands: Commands,
+  mut qry: Query<
+    (
+      Entity,
+      &mut StatusEffects,
+      Option<&mut Movement>,
+      Option<&mut TextureAtlasSprite>,
+    ),
+    Changed<StatusEffects>,
+  >,
+) {
+  for (entity, mut status_effects, mov, sprite) in qry.iter_mut() {
+    if status_effects.has(StatusEffectType::Stun) {
+      commands.entity(entity).insert(Stunned);
+    } else {
+      commands.entity(entity).remove::<Stunned>();
+    }
+
+    if let Some(mut mov) = mov {
+      let slow = status_effects.slow();
+      match status_effects.base_speed {
+        Some(base_speed) if slow <= 0. => {
+          mov.speed = base_speed;
+          status_effects.base_speed = None;
+        }
+        Some(base_speed) => mov.speed = base_speed * (1. - slow),
+        None if slow > 0. => {
+          status_effects.base_speed = Some(mov.speed);
+          mov.speed *= 1. - slow;
+        }
+        None => {}
+      }
+    }
+
+    if let Some(mut sprite) = sprite {
+      let tint = status_effects
+        .effects
+        .last()
+        .map(|e| e.definition.effect_type.tint());
+      match (tint, status_effects.base_color) {
+        (None, Some(base_color)) => {
+          sprite.color = base_color;
+          status_effects.base_color = None;
+        }
+        (Some(tint), None) => {
+          status_effects.base_color = Some(sprite.color);
+          sprite.color = tint;
+        }
+        (Some(tint), Some(_)) => sprite.color = tint,
+        (None, None) => {}
+      }
+    }
+  }
+}
+
+pub fn apply_damage(
+  victim: Entity,
+  combatant: &mut Combatant,
+  transform: &Transform,
+  hit: Hit,
+  source: Entity,
+  events: &mut EventWriter<CombatEvent>,
+) {
+  let overkill = (hit.amount - combatant.hp.max(0.)).max(0.);
+  combatant.hp -= hit.amount;
+
+  events.send(CombatEvent::DamageApplied(Damage {
+    victim,
+    source,
+    spell: hit.spell,
+    amount: hit.amount,
+    overkill,
+    position: transform.translation.xy(),
+    fatal: combatant.hp <= 0.,
+    damage_type: hit.damage_type,
+    crit: hit.crit,
+    depth: hit.depth,
+  }));
+
+  if combatant.hp <= 0. {
+    combatant.hp = 0.;
+    events.send(CombatEvent::CombatantKilled(victim, source));
+  }
+}
+
+fn contact_damage(
+  mut commands: Commands,
+  qry: Query<&ContactDamage>,
+  qry_faction: Query<&Faction>,
+  mut combatant_query: Query<
+    (Entity, &mut Combatant, &Transform, Option<&Resistances>),
+    (Without<Immortal>, Without<Invulnerable>),
+  >,
+  mut collisions: EventReader<CollisionEvent>,
+  mut events: EventWriter<CombatEvent>,
+) {
+  for event in collisions.iter().filter(|event| event.is_started()) {
+    let (entity_1, entity_2) = event.rigid_body_entities();
+    let (layers_1, layers_2) = event.collision_layers();
+    let (attacker, victim) = if layers_1.contains_group(PhysicsLayers::Enemies)
+      && layers_2.contains_group(PhysicsLayers::Player)
+    {
+      (entity_1, entity_2)
+    } else if layers_2.contains_group(PhysicsLayers::Enemies)
+      && layers_1.contains_group(PhysicsLayers::Player)
+    {
+      (entity_2, entity_1)
+    } else {
+      continue;
+    };
+
+    let hostile = match (qry_faction.get(attacker), qry_faction.get(victim)) {
+      (Ok(attacker_faction), Ok(victim_faction)) => attacker_faction.is_hostile_to(victim_faction),
+      _ => false,
+    };
+    if !hostile {
+      continue;
+    }
+
+    if let (Ok(contact), Ok((entity, mut c, transform, resistances))) =
+      (qry.get(attacker), combatant_query.get_mut(victim))
+    {
+      if c.hp <= 0. {
+        continue;
+      }
+      let multiplier = resistances.map_or(1., |r| r.multiplier(DamageType::Physical));
+      apply_damage(
+        entity,
+        &mut c,
+        transform,
+        Hit {
+          amount: (contact.damage * multiplier).max(0.),
+          damage_type: DamageType::Physical,
+          crit: false,
+          spell: None,
+          depth: 0,
+        },
+        attacker,
+        &mut events,
+      );
+      commands
+        .entity(entity)
+        .insert(Invulnerable::from_seconds(contact.invulnerability));
+    }
+  }
+}
+
+fn tick_stagger(
+  mut commands: Commands,
+  time: Res<GameTime>,
+  mut qry: Query<(Entity, &mut Staggered)>,
+) {
+  for (entity, mut staggered) in qry.iter_mut() {
+    staggered.timer.tick(time.delta());
+    if staggered.timer.finished() {
+      commands.entity(entity).remove::<Staggered>();
+    }
+  }
+}
+
+fn tick_invulnerability(
+  mut commands: Commands,
+  time: Res<GameTime>,
+  mut qry: Query<(Entity, &mut Invulnerable)>,
+) {
+  for (entity, mut invulnerable) in qry.iter_mut() {
+    invulnerable.timer.tick(time.delta());
+    if invulnerable.timer.finished() {
+      commands.entity(entity).remove::<Invulnerable>();
+    }
+  }
+}
+
+fn move_projectiles(
+  mut commands: Commands,
+  time: Res<GameTime>,
+  mut qry: Query<(Entity, &Projectile, &mut Transform)>,
+) {
+  for (entity, projectile, mut transform) in qry.iter_mut() {
+    let step = projectile.direction * projectile.velocity * time.delta_seconds();
+    transform.translation += Vec3::from((step, 0.));
+
+    if projectile.hits_remaining == 0
+      || (transform.translation.xy() - projectile.origin).length() >= projectile.max_range
+    {
+      commands.entity(entity).despawn_recursive();
+    }
+  }
+}
+
+fn stop_projectiles(
+  mut commands: Commands,
+  qry: Query<&Projectile>,
+  mut events: EventReader<CollisionEvent>,
+) {
+  events
+    .iter()
+    .filter(|event| event.is_started())
+    .filter_map(|event| {
+      let (entity_1, entity_2) = event.rigid_body_entities();
+      let (layers_1, layers_2) = event.collision_layers();
+      if layers_1.contains_group(PhysicsLayers::Attacks)
+        && layers_2.contains_group(PhysicsLayers::World)
+      {
+        Some(entity_1)
+      } else if layers_2.contains_group(PhysicsLayers::Attacks)
+        && layers_1.contains_group(PhysicsLayers::World)
+      {
+        Some(entity_2)
+      } else {
+        None
+      }
+    })
+    .for_each(|attack| {
+      if qry.get(attack).is_ok() {
+        commands.entity(attack).despawn_recursive();
+      }
+    });
+}
+
+fn tick_cooldowns(time: Res<GameTime>, mut qry: Query<&mut Spellbook>) {
+  for mut spellbook in qry.iter_mut() {
+    for spell in spellbook.spells.values_mut() {
+      if let SpellStatus::Cooldown(timer) = &mut spell.status {
+        timer.tick(time.delta());
+        if timer.finished() {
+          spell.status = SpellStatus::Ready;
+        }
+      }
+      if let Some(timer) = &mut spell.combo_timer {
+        timer.tick(time.delta());
+        if timer.finished() {
+          spell.combo_timer = None;
+        }
+      }
+    }
+  }
+}
+
+fn regenerate_mana(time: Res<GameTime>, mut qry: Query<&mut Mana>) {
+  for mut mana in qry.iter_mut() {
+    if mana.current < mana.max {
+      mana.current = (mana.current + mana.regen * time.delta_seconds()).min(mana.max);
+    }
+  }
+}
+
 fn show_damage(
   settings: Res<FlyingTextSettings>,
+  mut pool: ResMut<FlyingTextPool>,
   mut commands: Commands,
   mut events: EventReader<CombatEvent>,
+  mut qry: Query<(&mut Text, &mut Transform, &mut Visibility, &mut FlyingText)>,
 ) {
   for evt in events.iter() {
     match evt {
-      CombatEvent::DamageApplied(_victim, damage, pos, _fatal) => {
-        commands
-          .spawn_bundle(Text2dBundle {
-            text: Text::with_section(
-              format!("{:?}", *damage as i32),
-              settings.style.clone(),
-              settings.alignment,
-            ),
-            transform: Transform::from_translation(Vec3::from((
-              pos.clone(),
-              crate::z::FLYING_TEXT,
-            )))
-            .with_scale(Vec3::splat(0.25)),
-            ..Default::default()
-          })
-          .insert(TimedLife::from_seconds(0.1));
+      CombatEvent::DamageApplied(damage) => {
+        let mut value = if damage.amount <= 0. {
+          "Immune".to_owned()
+        } else {
+          format!("{:?}", damage.amount as i32)
+        };
+        if damage.crit {
+          value.push('!');
+        }
+        // killing blows are gold, crits are bigger and hang around longer
+        let color = if damage.fatal {
+          Color::GOLD
+        } else {
+          damage.damage_type.color()
+        };
+        let (scale, rise, lifetime) = match (damage.crit, damage.fatal) {
+          (true, true) => (0.5, 30., 1.0),
+          (true, false) => (0.45, 25., 0.9),
+          (false, true) => (0.35, 25., 0.8),
+          (false, false) => (0.25, 20., 0.6),
+        };
+        let flying_text = FlyingText {
+          timer: Timer::from_seconds(lifetime, false),
+          origin: damage.position,
+          rise,
+          scale,
+          color,
+        };
+        show_flying_text(&mut commands, &mut pool, &mut qry, &settings, value, flying_text);
+      }
+      CombatEvent::NotEnoughMana(_caster, pos) => {
+        let flying_text = FlyingText {
+          timer: Timer::from_seconds(0.6, false),
+          origin: *pos,
+          rise: 15.,
+          scale: 0.25,
+          color: Color::rgb(0.4, 0.6, 1.0),
+        };
+        let value = "No mana".to_owned();
+        show_flying_text(&mut commands, &mut pool, &mut qry, &settings, value, flying_text);
       }
       _ => {}
     }
   }
 }
 
+fn show_flying_text(
+  commands: &mut Commands,
+  pool: &mut FlyingTextPool,
+  qry: &mut Query<(&mut Text, &mut Transform, &mut Visibility, &mut FlyingText)>,
+  settings: &FlyingTextSettings,
+  value: String,
+  flying_text: FlyingText,
+) {
+  let text = Text::with_section(
+    value,
+    TextStyle {
+      color: flying_text.color,
+      ..settings.style.clone()
+    },
+    settings.alignment,
+  );
+  let position = Vec3::from((flying_text.origin, crate::z::FLYING_TEXT));
+  let transform = Transform::from_translation(position).with_scale(Vec3::splat(flying_text.scale));
+
+  // reuse a hidden text, or the oldest one if the pool is exhausted
+  let recycled = pool.free.pop().or_else(|| {
+    if pool.active.len() >= pool.max_size {
+      pool.active.pop_front()
+    } else {
+      None
+    }
+  });
+  let recycled = recycled.and_then(|entity| qry.get_mut(entity).ok().map(|r| (entity, r)));
+
+  match recycled {
+    Some((entity, (mut t, mut tf, mut visibility, mut ft))) => {
+      *t = text;
+      *tf = transform;
+      *ft = flying_text;
+      visibility.is_visible = true;
+      pool.active.push_back(entity);
+    }
+    None => {
+      let entity = commands
+        .spawn_bundle(Text2dBundle {
+          text,
+          transform,
+          ..Default::default()
+        })
+        .insert(flying_text)
+        .id();
+      pool.active.push_back(entity);
+    }
+  }
+}
+
+// rise, grow a little and fade out, then go back to the pool
+fn animate_flying_text(
+  time: Res<Time>,
+  mut pool: ResMut<FlyingTextPool>,
+  mut qry: Query<(
+    Entity,
+    &mut FlyingText,
+    &mut Text,
+    &mut Transform,
+    &mut Visibility,
+  )>,
+) {
+  for (entity, mut ft, mut text, mut transform, mut visibility) in qry.iter_mut() {
+    if !visibility.is_visible {
+      continue;
+    }
+    ft.timer.tick(time.delta());
+    let t = ft.timer.percent();
+
+    transform.translation.x = ft.origin.x;
+    transform.translation.y = ft.origin.y + ft.rise * t;
+    transform.scale = Vec3::splat(ft.scale * (1. + 0.3 * t));
+    let mut color = ft.color;
+    color.set_a(1. - t * t);
+    for section in text.sections.iter_mut() {
+      section.style.color = color;
+    }
+
+    if ft.timer.finished() {
+      visibility.is_visible = false;
+      pool.active.retain(|e| *e != entity);
+      pool.free.push(entity);
+    }
+  }
+}
+
 fn spawn_spell_stuff(
   mut commands: Commands,
   mut actions: EventReader<CombatAction>,
-  mut qry: Query<(&Spellbook, &Transform)>,
+  physics_world: PhysicsWorld,
+  mut qry: Query<(
+    &mut Spellbook,
+    &mut Transform,
+    Option<&Faction>,
+    Option<&CollisionShape>,
+    Option<&mut Mana>,
+  )>,
 ) {
   for action in actions.iter() {
     match action {
       CombatAction::PrepareSpell(entity, spell_type, dir) => {
-        if let Ok((spellbook, caster_transform)) = qry.get_mut(*entity) {
-          let spell = spellbook.spells.get(spell_type).expect("spell not found");
+        if let Ok((mut spellbook, caster_transform, _, _, _)) = qry.get_mut(*entity) {
+          spellbook.casts += 1;
+          spellbook.active = Some(spellbook.casts);
+          let instance = SpellInstance {
+            caster: *entity,
+            spell_type: *spell_type,
+            cast: spellbook.casts,
+          };
+          let spell = spellbook
+            .spells
+            .get_mut(spell_type)
+            .expect("spell not found");
+          spell.advance_combo();
           if let Some(sprite) = &spell.prepare_sprite {
             commands
               .spawn()
+              .insert(instance)
               .insert(
                 Transform::from_translation(Vec3::new(
                   caster_transform.translation.x,
@@ -236,11 +1321,34 @@ fn spawn_spell_stuff(
         }
       }
       CombatAction::CastSpell(entity, spell_type, dir) => {
-        if let Ok((spellbook, caster_transform)) = qry.get(*entity) {
-          let spell = spellbook.spells.get(spell_type).expect("spell not found");
-          if let Some(sprite) = &spell.cast_sprite {
+        if let Ok((mut spellbook, mut caster_transform, faction, shape, mana)) =
+          qry.get_mut(*entity)
+        {
+          let faction = faction.copied().unwrap_or(Faction::Neutral);
+          let instance = SpellInstance {
+            caster: *entity,
+            spell_type: *spell_type,
+            cast: spellbook.active.unwrap_or(spellbook.casts),
+          };
+          let spell = spellbook
+            .spells
+            .get_mut(spell_type)
+            .expect("spell not found");
+          spell.start_cooldown();
+          let spell = &spell.at_combo_stage(spell.combo_stage);
+          if let Some(mut mana) = mana {
+            mana.current = (mana.current - spell.cost).max(0.);
+          }
+          if spell.blink > 0. {
+            blink(&physics_world, &mut caster_transform, shape, *dir * spell.blink);
+          }
+          if spell.projectile_velocity > 0. {
+            spawn_projectile(&mut commands, instance, faction, spell, &caster_transform, dir);
+          } else if let Some(sprite) = &spell.cast_sprite {
+            let lifetime = spell.cast_duration.max(spell.ground_duration);
             commands
               .spawn()
+              .insert(instance)
               .insert(
                 Transform::from_translation(Vec3::new(
                   caster_transform.translation.x,
@@ -253,54 +1361,60 @@ fn spawn_spell_stuff(
                 )),
               )
               .insert(GlobalTransform::default())
-              .insert(TimedLife::from_seconds(spell.cast_duration))
+              .insert(TimedLife::from_seconds(lifetime))
               .insert(RigidBody::Sensor)
-              .insert(
-                CollisionLayers::none()
-                  .with_group(PhysicsLayers::Attacks)
-                  .with_mask(PhysicsLayers::Enemies),
-              )
+              .insert(attack_layers())
               .insert(AreaOfEffect {
                 caster: *entity,
+                spell: *spell_type,
+                area: spell.area,
+                faction,
                 damage_min: spell.damage_min,
                 damage_max: spell.damage_max,
-                tick_timer: if spell.dot {
-                  Timer::from_seconds(spell.damage_tick, true)
+                damage_type: spell.damage_type,
+                crit_chance: spell.crit_chance,
+                crit_multiplier: spell.crit_multiplier,
+                knockback: spell.knockback,
+                status_effects: spell.status_effects.clone(),
+                tick_timer: Timer::from_seconds(0.001, false),
+                dot_interval: if spell.dot {
+                  Some(spell.damage_tick)
                 } else {
-                  Timer::from_seconds(0.001, false)
+                  None
                 },
+                age: 0.,
+                lifetime,
+                last_ticks: HashMap::default(),
                 victims: Vec::new(),
               })
               .with_children(|parent| {
-                parent
-                  .spawn_bundle(SpriteSheetBundle {
-                    texture_atlas: sprite.texture_atlas.clone(),
-                    transform: Transform::from_translation(Vec3::new(
-                      sprite.translation.x,
-                      sprite.translation.y,
-                      0.0,
-                    )),
-                    ..Default::default()
-                  })
-                  .insert(spell.shape.clone())
-                  .insert(
-                    CollisionLayers::none()
-                      .with_group(PhysicsLayers::Attacks)
-                      .with_mask(PhysicsLayers::Enemies),
-                  )
+                let mut child = parent.spawn_bundle(SpriteSheetBundle {
+                  texture_atlas: sprite.texture_atlas.clone(),
+                  transform: Transform::from_translation(Vec3::new(
+                    sprite.translation.x,
+                    sprite.translation.y,
+                    0.0,
+                  )),
+                  ..Default::default()
+                });
+                child
                   .insert(AtlasAnimationDefinition {
                     start: sprite.start_frame,
                     end: sprite.end_frame,
                     fps: if sprite.repeatable {
                       sprite.fps
                     } else {
-                      (sprite.end_frame - sprite.start_frame + 1) as f32 / spell.cast_duration
+                      (sprite.end_frame - sprite.start_frame + 1) as f32 / lifetime
                     },
                     repeat: sprite.repeatable,
                     random_start: false,
                     repeat_from: None,
                   })
                   .insert(AtlasAnimation::default());
+                // other areas pick their victims in `find_area_victims`
+                if spell.area == AreaShape::Sensor {
+                  child.insert(spell.shape.clone()).insert(attack_layers());
+                }
               });
           }
         } else {
@@ -308,11 +1422,21 @@ fn spawn_spell_stuff(
         }
       }
       CombatAction::RecoverFromSpell(entity, spell_type, dir) => {
-        if let Ok((spellbook, caster_transform)) = qry.get(*entity) {
-          let spell = spellbook.spells.get(spell_type).expect("spell not found");
-          if let Some(sprite) = &spell.cast_sprite {
+        if let Ok((mut spellbook, caster_transform, _, _, _)) = qry.get_mut(*entity) {
+          let spell = spellbook
+            .spells
+            .get_mut(spell_type)
+            .expect("spell not found");
+          spell.open_combo_window();
+          let spell = &spell.at_combo_stage(spell.combo_stage);
+          if let Some(sprite) = &spell.recovery_sprite {
             commands
               .spawn()
+              .insert(SpellInstance {
+                caster: *entity,
+                spell_type: *spell_type,
+                cast: spellbook.active.unwrap_or(spellbook.casts),
+              })
               .insert(
                 Transform::from_translation(Vec3::new(
                   caster_transform.translation.x,
@@ -356,13 +1480,170 @@ fn spawn_spell_stuff(
           warn!("spellcaster not found, cannot prepare spell");
         }
       }
-      CombatAction::CancelSpell(_entity) => {
-        // TODO: despawn?
+      // handled by `cancel_spells`
+      CombatAction::CancelSpell(_) => {}
+    }
+  }
+}
+
+// takes back everything the cancelled cast spawned, projectiles and ground effects included,
+// so no damage lands after the cancel
+fn cancel_spells(
+  mut commands: Commands,
+  mut actions: EventReader<CombatAction>,
+  mut qry: Query<&mut Spellbook>,
+  mut qry_instances: Query<(Entity, &SpellInstance, Option<&mut AreaOfEffect>)>,
+) {
+  for action in actions.iter() {
+    let entity = match action {
+      CombatAction::CancelSpell(entity) => *entity,
+      _ => continue,
+    };
+    let cast = match qry.get_mut(entity) {
+      Ok(mut spellbook) => spellbook.active.take(),
+      _ => continue,
+    };
+    for (spell_entity, instance, aoe) in qry_instances.iter_mut() {
+      if instance.caster == entity && Some(instance.cast) == cast {
+        if let Some(mut aoe) = aoe {
+          aoe.expire();
+        }
+        commands.entity(spell_entity).despawn_recursive();
       }
     }
   }
 }
 
+// teleports the caster, stopping short of any wall in the way
+fn blink(
+  physics_world: &PhysicsWorld,
+  transform: &mut Transform,
+  shape: Option<&CollisionShape>,
+  offset: Vec2,
+) {
+  let origin = transform.translation.xy();
+  let mut offset = offset;
+  if let Some(shape) = shape {
+    let result = physics_world.shape_cast_with_filter(
+      shape,
+      Vec3::from((origin, 0.)),
+      Quat::IDENTITY,
+      Vec3::from((offset, 0.)),
+      CollisionLayers::none()
+        .with_group(PhysicsLayers::MovementSensor)
+        .with_mask(PhysicsLayers::World),
+      |_| true,
+    );
+    if let Some(ShapeCastCollisionType::Collided(info)) = result.map(|hit| hit.collision_type) {
+      offset = (info.self_end_position.xy() - origin) * 0.9;
+    }
+  }
+  transform.translation.x += offset.x;
+  transform.translation.y += offset.y;
+}
+
+// attacks touch every combatant, factions decide who actually gets hurt
+fn attack_layers() -> CollisionLayers {
+  CollisionLayers::none()
+    .with_group(PhysicsLayers::Attacks)
+    .with_mask(PhysicsLayers::Player)
+    .with_mask(PhysicsLayers::Enemies)
+    .with_mask(PhysicsLayers::World)
+}
+
+fn spawn_projectile(
+  commands: &mut Commands,
+  instance: SpellInstance,
+  faction: Faction,
+  spell: &Spell,
+  caster_transform: &Transform,
+  dir: &Vec2,
+) {
+  let lifetime = spell.max_range / spell.projectile_velocity;
+  let (caster, spell_type) = (instance.caster, instance.spell_type);
+  let mut projectile = commands.spawn();
+  projectile
+    .insert(instance)
+    .insert(
+      Transform::from_translation(Vec3::new(
+        caster_transform.translation.x,
+        caster_transform.translation.y,
+        crate::z::PLAYER_ATTACK,
+      ))
+      .with_rotation(Quat::from_rotation_arc(
+        Vec3::X,
+        Vec3::new(dir.x, dir.y, 0.0),
+      )),
+    )
+    .insert(GlobalTransform::default())
+    .insert(TimedLife::from_seconds(lifetime))
+    .insert(RigidBody::Sensor)
+    .insert(attack_layers())
+    .insert(AreaOfEffect {
+      caster,
+      spell: spell_type,
+      area: AreaShape::Sensor,
+      faction,
+      damage_min: spell.damage_min,
+      damage_max: spell.damage_max,
+      damage_type: spell.damage_type,
+      crit_chance: spell.crit_chance,
+      crit_multiplier: spell.crit_multiplier,
+      knockback: spell.knockback,
+      status_effects: spell.status_effects.clone(),
+      // check for new victims every frame
+      tick_timer: Timer::from_seconds(0.001, true),
+      dot_interval: None,
+      age: 0.,
+      lifetime,
+      last_ticks: HashMap::default(),
+      victims: Vec::new(),
+    })
+    .insert(Projectile {
+      origin: caster_transform.translation.xy(),
+      direction: dir.normalize(),
+      velocity: spell.projectile_velocity,
+      max_range: spell.max_range,
+      hits_remaining: spell.pierce + 1,
+      hit: Vec::new(),
+    });
+
+  match &spell.projectile_sprite {
+    Some(sprite) => {
+      projectile.with_children(|parent| {
+        parent
+          .spawn_bundle(SpriteSheetBundle {
+            texture_atlas: sprite.texture_atlas.clone(),
+            transform: Transform::from_translation(Vec3::new(
+              sprite.translation.x,
+              sprite.translation.y,
+              0.0,
+            )),
+            ..Default::default()
+          })
+          .insert(spell.shape.clone())
+          .insert(attack_layers())
+          .insert(AtlasAnimationDefinition {
+            start: sprite.start_frame,
+            end: sprite.end_frame,
+            fps: if sprite.repeatable {
+              sprite.fps
+            } else {
+              (sprite.end_frame - sprite.start_frame + 1) as f32 / lifetime
+            },
+            repeat: sprite.repeatable,
+            random_start: false,
+            repeat_from: None,
+          })
+          .insert(AtlasAnimation::default());
+      });
+    }
+    None => {
+      projectile.insert(spell.shape.clone());
+    }
+  }
+}
+
 fn setup(mut settings: ResMut<FlyingTextSettings>, asset_server: Res<AssetServer>) {
   let font = asset_server.load("FiraMono-Medium.ttf");
   *settings = FlyingTextSettings {
@@ -387,10 +1668,312 @@ impl Plugin for CombatPlugin {
       .add_event::<CombatEvent>()
       .add_event::<CombatAction>()
       .init_resource::<FlyingTextSettings>()
+      .init_resource::<FlyingTextPool>()
       .add_startup_system(setup)
       .add_system(show_damage)
-      .add_system(spawn_spell_stuff)
+      .add_system(animate_flying_text)
+      .add_system(spawn_spell_stuff.label("spawn_spell_stuff"))
+      .add_system(cancel_spells.label("cancel_spells"))
+      .add_system(tick_cooldowns)
+      .add_system(regenerate_mana)
+      .add_system(contact_damage)
+      .add_system(tick_invulnerability.label("tick_invulnerability"))
+      .add_system(tick_stagger)
+      .add_system(move_projectiles)
+      .add_system(stop_projectiles.after("damage_victims"))
       .add_system(find_victims.label("find_victims"))
-      .add_system(damage_victims.label("damage_victims").after("find_victims"));
+      .add_system(find_area_victims.label("find_victims"))
+      .add_system(
+        damage_victims
+          .label("damage_victims")
+          .after("find_victims")
+          .after("spawn_spell_stuff")
+          .after("cancel_spells"),
+      )
+      .add_system(tick_status_effects)
+      .add_system(update_status_effects);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn area(dot_interval: Option<f32>, lifetime: f32) -> AreaOfEffect {
+    AreaOfEffect {
+      caster: Entity::from_raw(0),
+      spell: SpellType::BurningGround,
+      area: AreaShape::Sensor,
+      faction: Faction::Player,
+      damage_min: 1.,
+      damage_max: 1.,
+      damage_type: DamageType::Fire,
+      crit_chance: 0.,
+      crit_multiplier: 1.,
+      knockback: 0.,
+      status_effects: Vec::new(),
+      tick_timer: Timer::from_seconds(0.001, false),
+      dot_interval,
+      age: 0.,
+      lifetime,
+      last_ticks: HashMap::default(),
+      victims: Vec::new(),
+    }
+  }
+
+  // ticks the area like `damage_victims` does and returns who got hit
+  fn step(aoe: &mut AreaOfEffect, seconds: f32) -> Vec<Entity> {
+    let due: Vec<Entity> = aoe
+      .tick(Duration::from_secs_f32(seconds))
+      .into_iter()
+      .map(|(_, victim)| victim)
+      .collect();
+    for victim in due.iter() {
+      aoe.record_tick(*victim);
+    }
+    due
+  }
+
+  #[test]
+  fn damage_over_time_ticks_on_entering_and_then_every_interval() {
+    let victim = Entity::from_raw(1);
+    let mut aoe = area(Some(0.5), 5.);
+    aoe.victims.push(victim);
+
+    assert_eq!(step(&mut aoe, 0.1), vec![victim]);
+    assert!(step(&mut aoe, 0.2).is_empty());
+    assert!(step(&mut aoe, 0.2).is_empty());
+    assert_eq!(step(&mut aoe, 0.15), vec![victim]);
+    assert!(step(&mut aoe, 0.4).is_empty());
+    assert_eq!(step(&mut aoe, 0.2), vec![victim]);
+  }
+
+  #[test]
+  fn each_victim_ticks_on_its_own_schedule() {
+    let (first, second) = (Entity::from_raw(1), Entity::from_raw(2));
+    let mut aoe = area(Some(0.5), 5.);
+    aoe.victims.push(first);
+    assert_eq!(step(&mut aoe, 0.1), vec![first]);
+
+    // walking in later doesn't have to wait for the first victim's next tick
+    assert!(step(&mut aoe, 0.2).is_empty());
+    aoe.victims.push(second);
+    assert_eq!(step(&mut aoe, 0.1), vec![second]);
+    assert_eq!(step(&mut aoe, 0.25), vec![first]);
+    assert_eq!(step(&mut aoe, 0.3), vec![second]);
+
+    // leaving and coming back doesn't reset the schedule
+    aoe.victims.retain(|victim| *victim != first);
+    assert!(step(&mut aoe, 0.1).is_empty());
+    aoe.victims.push(first);
+    assert!(step(&mut aoe, 0.05).is_empty());
+    assert_eq!(step(&mut aoe, 0.3), vec![first]);
+  }
+
+  #[test]
+  fn ground_effect_stops_ticking_when_it_expires() {
+    let victim = Entity::from_raw(1);
+    let mut aoe = area(Some(0.5), 1.);
+    aoe.victims.push(victim);
+
+    assert_eq!(step(&mut aoe, 0.1), vec![victim]);
+    assert_eq!(step(&mut aoe, 0.6), vec![victim]);
+    assert!(step(&mut aoe, 0.2).is_empty());
+    assert!(step(&mut aoe, 0.4).is_empty());
+    assert!(step(&mut aoe, 1.).is_empty());
+  }
+
+  #[test]
+  fn areas_without_damage_over_time_hit_once() {
+    let victim = Entity::from_raw(1);
+    let mut aoe = area(None, 5.);
+    aoe.victims.push(victim);
+
+    assert_eq!(step(&mut aoe, 0.1), vec![victim]);
+    assert!(step(&mut aoe, 0.1).is_empty());
+    assert!(step(&mut aoe, 1.).is_empty());
+  }
+
+  fn effect(
+    effect_type: StatusEffectType,
+    duration: f32,
+    max_stacks: u32,
+  ) -> StatusEffectDefinition {
+    StatusEffectDefinition {
+      effect_type,
+      duration,
+      tick_interval: 1.,
+      damage_per_tick: 5.,
+      slow: 0.,
+      max_stacks,
+    }
+  }
+
+  #[test]
+  fn damaging_effects_tick_until_they_wear_off() {
+    let (poison, source) = (effect(StatusEffectType::Poison, 2.5, 1), Entity::from_raw(0));
+    let mut status_effects = StatusEffects::default();
+    status_effects.apply(&poison, source, None, 10.);
+    let poison = &mut status_effects.effects[0];
+
+    assert!(!poison.is_due(10.5));
+    assert!(poison.is_due(11.1));
+    assert!(poison.tick(11.1));
+    assert!(!poison.tick(11.2));
+    assert!(poison.tick(12.1));
+    // the third tick would come after the effect wore off
+    assert!(poison.is_due(12.6));
+    assert!(!poison.tick(13.1));
+  }
+
+  #[test]
+  fn effects_without_damage_only_wear_off() {
+    let mut status_effects = StatusEffects::default();
+    status_effects.apply(&effect(StatusEffectType::Stun, 2., 1), Entity::from_raw(0), None, 0.);
+    let stun = &mut status_effects.effects[0];
+
+    assert!(!stun.is_due(1.5));
+    assert!(!stun.tick(1.5));
+    assert!(stun.is_due(2.));
+  }
+
+  #[test]
+  fn reapplying_an_effect_stacks_and_refreshes_it() {
+    let (burn, source) = (effect(StatusEffectType::Burn, 3., 2), Entity::from_raw(0));
+    let mut status_effects = StatusEffects::default();
+    status_effects.apply(&burn, source, None, 0.);
+    status_effects.apply(&burn, source, None, 1.);
+    status_effects.apply(&burn, source, None, 2.);
+    assert_eq!(status_effects.effects.len(), 1);
+    assert_eq!(status_effects.effects[0].stacks, 2);
+    assert_eq!(status_effects.effects[0].expires, 5.);
+    // the tick schedule isn't reset by refreshing
+    assert_eq!(status_effects.effects[0].next_tick, 1.);
+
+    // burns from someone else are tracked on their own
+    status_effects.apply(&burn, Entity::from_raw(1), None, 2.);
+    assert_eq!(status_effects.effects.len(), 2);
+    assert!(status_effects.has(StatusEffectType::Burn));
+    assert!(!status_effects.has(StatusEffectType::Stun));
+  }
+
+  #[test]
+  fn only_the_players_side_can_break_walls() {
+    assert!(Faction::Player.can_damage(&Faction::Breakable));
+    assert!(Faction::Ally.can_damage(&Faction::Breakable));
+    assert!(!Faction::Monsters.can_damage(&Faction::Breakable));
+    assert!(!Faction::Neutral.can_damage(&Faction::Breakable));
+    assert!(Faction::Monsters.can_damage(&Faction::Neutral));
+  }
+
+  #[test]
+  fn cones_contain_targets_within_half_their_angle_either_side() {
+    let cone = AreaShape::Cone {
+      angle: 90.,
+      range: 50.,
+    };
+    let (origin, direction) = (Vec2::new(10., 10.), Vec2::X);
+    let at = |x: f32, y: f32| cone.contains(origin, direction, origin + Vec2::new(x, y));
+
+    assert!(at(40., 0.));
+    assert!(at(30., 29.));
+    assert!(at(30., -29.));
+    assert!(!at(30., 31.));
+    assert!(!at(-30., 0.));
+    assert!(!at(51., 0.));
+  }
+
+  #[test]
+  fn rings_leave_out_their_centre() {
+    let ring = AreaShape::Ring {
+      inner: 20.,
+      outer: 40.,
+    };
+    let origin = Vec2::new(-5., 5.);
+    let at = |x: f32, y: f32| ring.contains(origin, Vec2::X, origin + Vec2::new(x, y));
+
+    assert!(at(0., 30.));
+    assert!(at(-20., 0.));
+    assert!(at(0., -40.));
+    assert!(!at(10., 10.));
+    assert!(!at(30., 30.));
+  }
+
+  #[test]
+  fn cancelling_a_spell_stops_its_projectiles_in_flight() {
+    let mut world = World::default();
+    world.insert_resource(GameTime::default());
+    world.insert_resource(GameRng::from_seed(0));
+    world.insert_resource(Events::<CombatAction>::default());
+    world.insert_resource(Events::<CombatEvent>::default());
+    let mut spawn_victim = || {
+      world
+        .spawn()
+        .insert(Combatant {
+          hp: 100.,
+          hp_max: 100.,
+        })
+        .insert(Transform::default())
+        .id()
+    };
+    let (first_victim, second_victim) = (spawn_victim(), spawn_victim());
+    let caster = world
+      .spawn()
+      .insert(Transform::default())
+      .insert(Spellbook {
+        casts: 2,
+        active: Some(2),
+        ..Default::default()
+      })
+      .id();
+
+    // one projectile from an earlier cast and one from the cast being cancelled, both about
+    // to hit, damage over time makes them due without the clock moving
+    let mut fire = |cast: u32, victim: Entity| {
+      let mut aoe = area(Some(1.), 5.);
+      aoe.caster = caster;
+      aoe.victims.push(victim);
+      world
+        .spawn()
+        .insert(SpellInstance {
+          caster,
+          spell_type: SpellType::PiercingBolt,
+          cast,
+        })
+        .insert(aoe)
+        .insert(Projectile {
+          origin: Vec2::ZERO,
+          direction: Vec2::X,
+          velocity: 100.,
+          max_range: 100.,
+          hits_remaining: 1,
+          hit: Vec::new(),
+        })
+        .id()
+    };
+    fire(1, first_victim);
+    let cancelled = fire(2, second_victim);
+
+    world
+      .get_resource_mut::<Events<CombatAction>>()
+      .unwrap()
+      .send(CombatAction::CancelSpell(caster));
+    SystemStage::single_threaded()
+      .with_system(cancel_spells.label("cancel_spells"))
+      .with_system(damage_victims.after("cancel_spells"))
+      .run(&mut world);
+
+    let events = world.get_resource::<Events<CombatEvent>>().unwrap();
+    let victims: Vec<Entity> = events
+      .get_reader()
+      .iter(events)
+      .filter_map(|event| match event {
+        CombatEvent::DamageApplied(damage) => Some(damage.victim),
+        _ => None,
+      })
+      .collect();
+    assert_eq!(victims, vec![first_victim]);
+    assert!(world.get_entity(cancelled).is_none());
+    assert!(world.get::<Spellbook>(caster).unwrap().active.is_none());
   }
 }
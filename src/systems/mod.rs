@@ -5,6 +5,10 @@ mod combat;
 mod mouse;
 mod movement;
 mod physics;
+mod rng;
+mod spells;
+mod time;
+mod triggers;
 mod utils;
 
 pub use animation::*;
@@ -14,4 +18,8 @@ pub use combat::*;
 pub use mouse::*;
 pub use movement::*;
 pub use physics::*;
+pub use rng::*;
+pub use spells::*;
+pub use time::*;
+pub use triggers::*;
 pub use utils::*;
@@ -1,3 +1,4 @@
+use crate::systems::{GameTime, Staggered, Stunned};
 use bevy::{math::Vec3Swizzles, prelude::*};
 use heron::{PhysicMaterial, Velocity};
 
@@ -9,7 +10,13 @@ pub struct Movement {
   pub path_backlog: Vec<Vec2>,
 }
 
-fn movement(time: Res<Time>, mut qry: Query<(&mut Movement, &mut Transform), Without<Velocity>>) {
+fn movement(
+  time: Res<GameTime>,
+  mut qry: Query<
+    (&mut Movement, &mut Transform),
+    (Without<Velocity>, Without<Stunned>, Without<Staggered>),
+  >,
+) {
   for (mut mov, mut transform) in qry.iter_mut() {
     if !mov.enabled || mov.speed == 0.0 {
       continue;
@@ -37,8 +44,11 @@ fn movement(time: Res<Time>, mut qry: Query<(&mut Movement, &mut Transform), Wit
 }
 
 fn movement_phys(
-  time: Res<Time>,
-  mut qry: Query<(&mut Movement, &Transform, &PhysicMaterial, &mut Velocity)>,
+  time: Res<GameTime>,
+  mut qry: Query<
+    (&mut Movement, &Transform, &PhysicMaterial, &mut Velocity),
+    (Without<Stunned>, Without<Staggered>),
+  >,
 ) {
   for (mut mov, transform, mat, mut v) in qry.iter_mut() {
     if !mov.enabled || mov.speed == 0.0 {
@@ -1,7 +1,7 @@
 use bevy::prelude::*;
 use heron::prelude::*;
 
-#[derive(PhysicsLayer)]
+#[derive(PhysicsLayer, Copy, Clone, Debug)]
 pub enum PhysicsLayers {
   World, // walls
   Player,
@@ -0,0 +1,53 @@
+use bevy::prelude::*;
+use rand::prelude::*;
+
+// every kind of gameplay randomness draws from its own stream, so e.g. an extra animation
+// doesn't change which enemies spawn
+pub struct GameRng {
+  pub seed: u64,
+  pub level: StdRng,
+  pub combat: StdRng,
+  pub spawning: StdRng,
+  pub animation: StdRng,
+}
+
+impl GameRng {
+  pub fn from_seed(seed: u64) -> Self {
+    let stream =
+      |index: u64| StdRng::seed_from_u64(seed ^ index.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+    GameRng {
+      seed,
+      level: stream(1),
+      combat: stream(2),
+      spawning: stream(3),
+      animation: stream(4),
+    }
+  }
+}
+
+// set SEED to replay a run, otherwise every run gets a random one
+fn run_seed() -> u64 {
+  std::env::var("SEED")
+    .ok()
+    .and_then(|seed| seed.parse().ok())
+    .unwrap_or_else(|| rand::thread_rng().gen())
+}
+
+impl Default for GameRng {
+  fn default() -> Self {
+    GameRng::from_seed(run_seed())
+  }
+}
+
+// every run starts from a fresh seed, same seed and same inputs give the same run
+pub fn reset_rng(mut rng: ResMut<GameRng>) {
+  *rng = GameRng::from_seed(run_seed());
+  info!("game seed: {}", rng.seed);
+}
+
+pub struct RngPlugin;
+impl Plugin for RngPlugin {
+  fn build(&self, app: &mut App) {
+    app.init_resource::<GameRng>();
+  }
+}
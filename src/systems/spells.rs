@@ -0,0 +1,389 @@
+use crate::systems::{
+  AreaShape, AtlasAnimationDefinition, ComboStage, DamageType, Spell, SpellSprite, SpellStatus,
+  SpellType, Spellbook, StatusEffectDefinition, Summon,
+};
+use bevy::{
+  asset::{AssetLoader, LoadContext, LoadedAsset},
+  prelude::*,
+  reflect::TypeUuid,
+  utils::{BoxedFuture, HashMap},
+};
+use heron::prelude::*;
+use serde::Deserialize;
+
+// a spell as it is written in `assets/spells/*.spell.ron`
+#[derive(Deserialize, TypeUuid)]
+#[uuid = "6f0c3d1e-94a7-4b52-8d2a-1c5be7a0f913"]
+pub struct SpellDefinition {
+  pub spell_type: SpellType,
+  pub damage_min: f32,
+  pub damage_max: f32,
+  pub damage_type: DamageType,
+  #[serde(default)]
+  pub crit_chance: f32,
+  #[serde(default = "default_crit_multiplier")]
+  pub crit_multiplier: f32,
+  #[serde(default)]
+  pub knockback: f32,
+  #[serde(default)]
+  pub cost: f32,
+  #[serde(default)]
+  pub status_effects: Vec<StatusEffectDefinition>,
+  #[serde(default)]
+  pub damage_tick: Option<f32>, // only set for damage over time
+  #[serde(default)]
+  pub ground_duration: f32,
+  pub prepare_duration: f32,
+  pub cast_duration: f32,
+  pub recovery_duration: f32,
+  #[serde(default)]
+  pub cooldown: f32,
+  pub atlas: AtlasDefinition,
+  pub prepare_sprite: Option<SpriteDefinition>,
+  pub cast_sprite: Option<SpriteDefinition>,
+  pub recovery_sprite: Option<SpriteDefinition>,
+  #[serde(default)]
+  pub projectile_sprite: Option<SpriteDefinition>,
+  pub shape: ShapeDefinition,
+  #[serde(default)]
+  pub area: AreaShape,
+  #[serde(default)]
+  pub projectile: Option<ProjectileDefinition>,
+  #[serde(default)]
+  pub blink: f32,
+  #[serde(default)]
+  pub combo: Vec<ComboStageDefinition>,
+  #[serde(default)]
+  pub combo_window: f32,
+  #[serde(default)]
+  pub summon: Option<SummonDefinition>,
+}
+
+fn default_crit_multiplier() -> f32 {
+  2.
+}
+
+#[derive(Deserialize)]
+pub struct AtlasDefinition {
+  pub path: String,
+  pub tile_size: (f32, f32),
+  pub columns: usize,
+  pub rows: usize,
+}
+impl AtlasDefinition {
+  pub fn build(&self, asset_server: &AssetServer) -> TextureAtlas {
+    TextureAtlas::from_grid(
+      asset_server.load(self.path.as_str()),
+      Vec2::new(self.tile_size.0, self.tile_size.1),
+      self.columns,
+      self.rows,
+    )
+  }
+}
+
+#[derive(Deserialize)]
+pub struct SpriteDefinition {
+  pub frames: (usize, usize), // first and last frame, inclusive
+  #[serde(default)]
+  pub repeatable: bool,
+  #[serde(default)]
+  pub fps: f32, // only used if repeatable
+  #[serde(default)]
+  pub translation: (f32, f32),
+}
+
+#[derive(Deserialize)]
+pub enum ShapeDefinition {
+  Cuboid { half_extends: (f32, f32) },
+  Sphere { radius: f32 },
+}
+impl ShapeDefinition {
+  pub fn build(&self) -> CollisionShape {
+    match *self {
+      ShapeDefinition::Cuboid { half_extends } => CollisionShape::Cuboid {
+        half_extends: Vec3::new(half_extends.0, half_extends.1, 0.),
+        border_radius: None,
+      },
+      ShapeDefinition::Sphere { radius } => CollisionShape::Sphere { radius },
+    }
+  }
+}
+
+#[derive(Deserialize)]
+pub struct ComboStageDefinition {
+  pub damage_min: f32,
+  pub damage_max: f32,
+  pub cast_sprite: Option<SpriteDefinition>,
+  #[serde(default)]
+  pub recovery_sprite: Option<SpriteDefinition>,
+  pub shape: ShapeDefinition,
+}
+
+#[derive(Deserialize)]
+pub struct SummonDefinition {
+  pub count: u32,
+  pub lifetime: f32,
+  pub max_hp: f32,
+  pub speed: f32,
+  pub atlas: AtlasDefinition,
+  pub idle: SpriteDefinition,
+  pub attack: SpellType, // has to be in the spell library as well
+  pub range: f32,
+}
+
+#[derive(Deserialize)]
+pub struct ProjectileDefinition {
+  pub velocity: f32,
+  #[serde(default)]
+  pub pierce: u32,
+  pub max_range: f32,
+}
+
+impl SpellDefinition {
+  // numbers the combat systems can't cope with are rejected when the file is loaded
+  pub fn validate(&self) -> Result<(), anyhow::Error> {
+    let damage = std::iter::once((self.damage_min, self.damage_max))
+      .chain(self.combo.iter().map(|stage| (stage.damage_min, stage.damage_max)));
+    for (damage_min, damage_max) in damage {
+      anyhow::ensure!(
+        damage_min <= damage_max,
+        "{:?}: damage_min {} is more than damage_max {}",
+        self.spell_type,
+        damage_min,
+        damage_max
+      );
+    }
+
+    let durations = [
+      ("damage_tick", self.damage_tick.unwrap_or(0.)),
+      ("ground_duration", self.ground_duration),
+      ("prepare_duration", self.prepare_duration),
+      ("cast_duration", self.cast_duration),
+      ("recovery_duration", self.recovery_duration),
+      ("cooldown", self.cooldown),
+      ("combo_window", self.combo_window),
+      ("summon lifetime", self.summon.as_ref().map_or(0., |summon| summon.lifetime)),
+    ]
+    .into_iter()
+    .chain(self.status_effects.iter().flat_map(|effect| {
+      [
+        ("status effect duration", effect.duration),
+        ("status effect tick_interval", effect.tick_interval),
+      ]
+    }));
+    for (name, seconds) in durations {
+      anyhow::ensure!(seconds >= 0., "{:?}: {} is negative", self.spell_type, name);
+    }
+    Ok(())
+  }
+
+  pub fn build(
+    &self,
+    texture_atlas: &Handle<TextureAtlas>,
+    summon_atlas: Option<Handle<TextureAtlas>>,
+  ) -> Spell {
+    let sprite = |definition: &Option<SpriteDefinition>| {
+      definition.as_ref().map(|sprite| SpellSprite {
+        texture_atlas: texture_atlas.clone(),
+        start_frame: sprite.frames.0,
+        end_frame: sprite.frames.1,
+        repeatable: sprite.repeatable,
+        fps: sprite.fps,
+        translation: Vec2::new(sprite.translation.0, sprite.translation.1),
+      })
+    };
+
+    Spell {
+      status: SpellStatus::Ready,
+      damage_min: self.damage_min,
+      damage_max: self.damage_max,
+      damage_type: self.damage_type,
+      crit_chance: self.crit_chance,
+      crit_multiplier: self.crit_multiplier,
+      knockback: self.knockback,
+      cost: self.cost,
+      status_effects: self.status_effects.clone(),
+      dot: self.damage_tick.is_some(),
+      damage_tick: self.damage_tick.unwrap_or(0.),
+      ground_duration: self.ground_duration,
+      prepare_duration: self.prepare_duration,
+      cast_duration: self.cast_duration,
+      recovery_duration: self.recovery_duration,
+      cooldown: self.cooldown,
+      prepare_sprite: sprite(&self.prepare_sprite),
+      cast_sprite: sprite(&self.cast_sprite),
+      recovery_sprite: sprite(&self.recovery_sprite),
+      projectile_sprite: sprite(&self.projectile_sprite),
+      shape: self.shape.build(),
+      area: self.area,
+      projectile_velocity: self.projectile.as_ref().map_or(0., |p| p.velocity),
+      pierce: self.projectile.as_ref().map_or(0, |p| p.pierce),
+      max_range: self.projectile.as_ref().map_or(0., |p| p.max_range),
+      blink: self.blink,
+      combo: self
+        .combo
+        .iter()
+        .map(|stage| ComboStage {
+          damage_min: stage.damage_min,
+          damage_max: stage.damage_max,
+          cast_sprite: sprite(&stage.cast_sprite),
+          recovery_sprite: sprite(&stage.recovery_sprite),
+          shape: stage.shape.build(),
+        })
+        .collect(),
+      combo_window: self.combo_window,
+      combo_stage: 0,
+      combo_timer: None,
+      summon: self
+        .summon
+        .as_ref()
+        .zip(summon_atlas)
+        .map(|(summon, texture_atlas)| Summon {
+          count: summon.count,
+          lifetime: summon.lifetime,
+          max_hp: summon.max_hp,
+          speed: summon.speed,
+          texture_atlas,
+          idle: AtlasAnimationDefinition {
+            start: summon.idle.frames.0,
+            end: summon.idle.frames.1,
+            fps: summon.idle.fps,
+            repeat: true,
+            random_start: true,
+            repeat_from: None,
+          },
+          attack: summon.attack,
+          range: summon.range,
+        }),
+    }
+  }
+}
+
+#[derive(Default)]
+pub struct SpellDefinitionLoader;
+impl AssetLoader for SpellDefinitionLoader {
+  fn load<'a>(
+    &'a self,
+    bytes: &'a [u8],
+    load_context: &'a mut LoadContext,
+  ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+    Box::pin(async move {
+      let definition = ron::de::from_bytes::<SpellDefinition>(bytes)?;
+      definition.validate()?;
+      load_context.set_default_asset(LoadedAsset::new(definition));
+      Ok(())
+    })
+  }
+
+  fn extensions(&self) -> &[&str] {
+    &["spell.ron"]
+  }
+}
+
+// spells built from the loaded definitions, new spellbooks are filled from here
+#[derive(Default)]
+pub struct SpellLibrary {
+  pub handles: Vec<HandleUntyped>, // keeps the definitions loaded
+  pub spells: HashMap<SpellType, Spell>,
+}
+
+fn load_spell_definitions(asset_server: Res<AssetServer>, mut library: ResMut<SpellLibrary>) {
+  match asset_server.load_folder("spells") {
+    Ok(handles) => library.handles = handles,
+    Err(err) => error!("could not load spell definitions: {:?}", err),
+  }
+}
+
+// also runs when a definition is saved while the game is running
+fn update_spell_library(
+  asset_server: Res<AssetServer>,
+  definitions: Res<Assets<SpellDefinition>>,
+  mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+  mut library: ResMut<SpellLibrary>,
+  mut events: EventReader<AssetEvent<SpellDefinition>>,
+  mut qry: Query<&mut Spellbook>,
+) {
+  for event in events.iter() {
+    let handle = match event {
+      AssetEvent::Created { handle } | AssetEvent::Modified { handle } => handle,
+      AssetEvent::Removed { .. } => continue,
+    };
+    let definition = match definitions.get(handle) {
+      Some(definition) => definition,
+      None => continue,
+    };
+
+    let texture_atlas = texture_atlases.add(definition.atlas.build(&asset_server));
+    let summon_atlas = definition
+      .summon
+      .as_ref()
+      .map(|summon| texture_atlases.add(summon.atlas.build(&asset_server)));
+    let spell = definition.build(&texture_atlas, summon_atlas);
+
+    // spellbooks that already have the spell pick up the changes but keep their cooldown, new
+    // spells are learned from the library by whoever can use them
+    for mut spellbook in qry.iter_mut() {
+      if let Some(existing) = spellbook.spells.get_mut(&definition.spell_type) {
+        let status = existing.status.clone();
+        *existing = spell.clone();
+        existing.status = status;
+      }
+    }
+    library.spells.insert(definition.spell_type, spell);
+  }
+}
+
+pub struct SpellsPlugin;
+impl Plugin for SpellsPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .add_asset::<SpellDefinition>()
+      .init_asset_loader::<SpellDefinitionLoader>()
+      .init_resource::<SpellLibrary>()
+      .add_startup_system(load_spell_definitions)
+      .add_system(update_spell_library);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn load(name: &str) -> SpellDefinition {
+    let path = format!("{}/assets/spells/{}", env!("CARGO_MANIFEST_DIR"), name);
+    let bytes = std::fs::read(&path).expect("should read spell definition");
+    ron::de::from_bytes(&bytes).expect("should parse spell definition")
+  }
+
+  #[test]
+  fn spell_assets_are_valid() {
+    let folder = format!("{}/assets/spells", env!("CARGO_MANIFEST_DIR"));
+    for entry in std::fs::read_dir(folder).expect("should find spell folder") {
+      let name = entry.expect("should read spell folder").file_name();
+      let name = name.to_str().expect("should be utf-8");
+      assert!(name.ends_with(".spell.ron"), "{} won't be loaded as a spell", name);
+      if let Err(err) = load(name).validate() {
+        panic!("{}: {}", name, err);
+      }
+    }
+  }
+
+  #[test]
+  fn bad_numbers_are_rejected() {
+    let mut definition = load("basic_attack.spell.ron");
+    definition.damage_min = definition.damage_max + 1.;
+    assert!(definition.validate().is_err());
+
+    let mut definition = load("basic_attack.spell.ron");
+    definition.combo[0].damage_max = f32::NAN;
+    assert!(definition.validate().is_err());
+
+    let mut definition = load("basic_attack.spell.ron");
+    definition.recovery_duration = -0.1;
+    assert!(definition.validate().is_err());
+
+    let mut definition = load("eye_bolt.spell.ron");
+    definition.status_effects[0].duration = -1.;
+    assert!(definition.validate().is_err());
+  }
+}
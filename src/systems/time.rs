@@ -0,0 +1,59 @@
+use bevy::{core::CoreSystem, prelude::*};
+use heron::PhysicsTime;
+use std::time::Duration;
+
+// clock for gameplay timers, it stands still during hitstop while ui keeps using `Time`
+#[derive(Default)]
+pub struct GameTime {
+  delta: Duration,
+  elapsed: f64, // gameplay seconds so far, frozen time doesn't count
+  hitstop: f32, // seconds of freeze left
+}
+
+impl GameTime {
+  pub fn delta(&self) -> Duration {
+    self.delta
+  }
+
+  pub fn delta_seconds(&self) -> f32 {
+    self.delta.as_secs_f32()
+  }
+
+  pub fn seconds_since_startup(&self) -> f64 {
+    self.elapsed
+  }
+
+  pub fn is_frozen(&self) -> bool {
+    self.hitstop > 0.
+  }
+
+  // freezes gameplay for a moment, a longer freeze that is already running is kept
+  pub fn freeze(&mut self, seconds: f32) {
+    self.hitstop = self.hitstop.max(seconds);
+  }
+}
+
+fn update_game_time(
+  time: Res<Time>,
+  mut game_time: ResMut<GameTime>,
+  mut physics_time: ResMut<PhysicsTime>,
+) {
+  // hitstop is measured in real time, otherwise it would never end
+  game_time.hitstop = (game_time.hitstop - time.delta_seconds()).max(0.);
+  let frozen = game_time.is_frozen();
+  game_time.delta = if frozen { Duration::ZERO } else { time.delta() };
+  game_time.elapsed += game_time.delta.as_secs_f64();
+  let scale = if frozen { 0. } else { 1. };
+  if physics_time.scale() != scale {
+    physics_time.set_scale(scale);
+  }
+}
+
+pub struct GameTimePlugin;
+impl Plugin for GameTimePlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .init_resource::<GameTime>()
+      .add_system_to_stage(CoreStage::First, update_game_time.after(CoreSystem::Time));
+  }
+}
@@ -0,0 +1,268 @@
+use crate::systems::{
+  apply_damage, CombatEvent, Combatant, DamageType, Faction, GameRng, Hit, Immortal,
+  Invulnerable, Resistances,
+};
+use bevy::{
+  asset::{AssetLoader, LoadContext, LoadedAsset},
+  math::Vec3Swizzles,
+  prelude::*,
+  reflect::TypeUuid,
+  utils::BoxedFuture,
+};
+use rand::Rng;
+use serde::Deserialize;
+
+// damage caused by a trigger can trigger again, but only this many times in a row
+pub const MAX_TRIGGER_DEPTH: u32 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+pub enum TriggerCondition {
+  OnHit,
+  OnKill,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub enum TriggerEffect {
+  // heal the attacker for a fraction of the damage dealt
+  Lifesteal { fraction: f32 },
+  // damage everything around the victim for a fraction of the victim's max hp
+  Explode {
+    max_hp_fraction: f32,
+    radius: f32,
+    damage_type: DamageType,
+  },
+  // jump from the victim to the closest other combatants for a fraction of the damage dealt
+  Chain {
+    jumps: u32,
+    range: f32,
+    damage_fraction: f32,
+  },
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Trigger {
+  pub condition: TriggerCondition,
+  #[serde(default = "always")]
+  pub chance: f32, // 0 to 1
+  pub effect: TriggerEffect,
+}
+
+fn always() -> f32 {
+  1.
+}
+
+// triggers that fire for hits dealt by this entity
+#[derive(Component, Clone, Default)]
+pub struct Triggers {
+  pub triggers: Vec<Trigger>,
+}
+
+// a passive as it is written in `assets/passives/*.passive`
+#[derive(Deserialize, TypeUuid)]
+#[uuid = "2b8e7f4c-51d3-4a9e-b0c6-7d2f18e3a5b4"]
+pub struct PassiveDefinition {
+  pub triggers: Vec<Trigger>,
+}
+
+#[derive(Default)]
+pub struct PassiveDefinitionLoader;
+impl AssetLoader for PassiveDefinitionLoader {
+  fn load<'a>(
+    &'a self,
+    bytes: &'a [u8],
+    load_context: &'a mut LoadContext,
+  ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+    Box::pin(async move {
+      let definition = ron::de::from_bytes::<PassiveDefinition>(bytes)?;
+      load_context.set_default_asset(LoadedAsset::new(definition));
+      Ok(())
+    })
+  }
+
+  fn extensions(&self) -> &[&str] {
+    &["passive"]
+  }
+}
+
+#[derive(Default)]
+pub struct PassiveLibrary {
+  pub handles: Vec<HandleUntyped>, // keeps the definitions loaded
+}
+impl PassiveLibrary {
+  // every loaded passive, there are no items to pick them up from yet
+  pub fn triggers(&self, definitions: &Assets<PassiveDefinition>) -> Triggers {
+    Triggers {
+      triggers: self
+        .handles
+        .iter()
+        .filter_map(|handle| definitions.get(handle.clone().typed::<PassiveDefinition>()))
+        .flat_map(|definition| definition.triggers.iter().cloned())
+        .collect(),
+    }
+  }
+}
+
+fn load_passive_definitions(asset_server: Res<AssetServer>, mut library: ResMut<PassiveLibrary>) {
+  match asset_server.load_folder("passives") {
+    Ok(handles) => library.handles = handles,
+    Err(err) => error!("could not load passive definitions: {:?}", err),
+  }
+}
+
+// damage caused by a trigger, applied in `apply_triggered_hits`
+pub struct TriggeredHit {
+  pub victim: Entity,
+  pub source: Entity,
+  pub hit: Hit,
+}
+
+fn fire_triggers(
+  mut rng: ResMut<GameRng>,
+  mut events: EventReader<CombatEvent>,
+  mut hits: EventWriter<TriggeredHit>,
+  qry_triggers: Query<(&Triggers, Option<&Faction>)>,
+  mut qry_combatants: Query<(Entity, &mut Combatant, &Transform, Option<&Faction>)>,
+) {
+  let rng = &mut rng.combat;
+  for evt in events.iter() {
+    let damage = match evt {
+      // kills are handled here as well, the killing blow knows how deep the proc chain is
+      CombatEvent::DamageApplied(damage) => damage,
+      _ => continue,
+    };
+    if damage.depth >= MAX_TRIGGER_DEPTH || damage.amount <= 0. {
+      continue;
+    }
+    let (triggers, faction) = match qry_triggers.get(damage.source) {
+      Ok(result) => result,
+      _ => continue,
+    };
+    let faction = faction.copied().unwrap_or(Faction::Neutral);
+
+    for trigger in triggers.triggers.iter() {
+      let condition_met = match trigger.condition {
+        TriggerCondition::OnHit => true,
+        TriggerCondition::OnKill => damage.fatal,
+      };
+      if !condition_met || !rng.gen_bool(trigger.chance.clamp(0., 1.) as f64) {
+        continue;
+      }
+
+      let hit = |amount: f32, damage_type: DamageType| Hit {
+        amount,
+        damage_type,
+        crit: false,
+        spell: damage.spell,
+        depth: damage.depth + 1,
+      };
+      // everything the attacker can hurt, except the victim that set the trigger off
+      let targets: Vec<(Entity, Vec2)> = qry_combatants
+        .iter()
+        .filter(|(entity, c, _, target_faction)| {
+          *entity != damage.victim
+            && *entity != damage.source
+            && c.hp > 0.
+            && faction.can_damage(&target_faction.copied().unwrap_or(Faction::Neutral))
+        })
+        .map(|(entity, _, transform, _)| (entity, transform.translation.xy()))
+        .collect();
+
+      match &trigger.effect {
+        TriggerEffect::Lifesteal { fraction } => {
+          // the attacker can die in the same frame, e.g. from a burn, and has to stay dead
+          if let Ok((_, mut c, _, _)) = qry_combatants.get_mut(damage.source) {
+            if c.hp > 0. {
+              c.hp = (c.hp + damage.amount * fraction).min(c.hp_max);
+            }
+          }
+        }
+        TriggerEffect::Explode {
+          max_hp_fraction,
+          radius,
+          damage_type,
+        } => {
+          let amount = match qry_combatants.get_mut(damage.victim) {
+            Ok((_, c, _, _)) => c.hp_max * max_hp_fraction,
+            _ => continue,
+          };
+          for (victim, position) in targets.iter() {
+            if position.distance(damage.position) <= *radius {
+              hits.send(TriggeredHit {
+                victim: *victim,
+                source: damage.source,
+                hit: hit(amount, *damage_type),
+              });
+            }
+          }
+        }
+        TriggerEffect::Chain {
+          jumps,
+          range,
+          damage_fraction,
+        } => {
+          let mut remaining = targets;
+          let mut from = damage.position;
+          for _ in 0..*jumps {
+            let next = remaining
+              .iter()
+              .enumerate()
+              .filter(|(_, (_, position))| position.distance(from) <= *range)
+              .min_by(|(_, (_, a)), (_, (_, b))| {
+                a.distance(from)
+                  .partial_cmp(&b.distance(from))
+                  .unwrap_or(std::cmp::Ordering::Equal)
+              })
+              .map(|(index, _)| index);
+            let (victim, position) = match next {
+              Some(index) => remaining.swap_remove(index),
+              None => break,
+            };
+            hits.send(TriggeredHit {
+              victim,
+              source: damage.source,
+              hit: hit(damage.amount * damage_fraction, damage.damage_type),
+            });
+            from = position;
+          }
+        }
+      }
+    }
+  }
+}
+
+fn apply_triggered_hits(
+  mut hits: EventReader<TriggeredHit>,
+  mut qry: Query<
+    (&mut Combatant, &Transform, Option<&Resistances>),
+    (Without<Immortal>, Without<Invulnerable>),
+  >,
+  mut events: EventWriter<CombatEvent>,
+) {
+  for triggered in hits.iter() {
+    if let Ok((mut c, transform, resistances)) = qry.get_mut(triggered.victim) {
+      if c.hp <= 0. {
+        continue;
+      }
+      let multiplier = resistances.map_or(1., |r| r.multiplier(triggered.hit.damage_type));
+      let hit = Hit {
+        amount: (triggered.hit.amount * multiplier).max(0.),
+        ..triggered.hit
+      };
+      apply_damage(triggered.victim, &mut c, transform, hit, triggered.source, &mut events);
+    }
+  }
+}
+
+pub struct TriggersPlugin;
+impl Plugin for TriggersPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .add_asset::<PassiveDefinition>()
+      .init_asset_loader::<PassiveDefinitionLoader>()
+      .init_resource::<PassiveLibrary>()
+      .add_event::<TriggeredHit>()
+      .add_startup_system(load_passive_definitions)
+      .add_system(fire_triggers.label("fire_triggers").after("damage_victims"))
+      .add_system(apply_triggered_hits.after("fire_triggers"));
+  }
+}